@@ -0,0 +1,330 @@
+use std::sync::Arc;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
+use tokio::sync::Mutex;
+
+use crate::{
+    base_message::{BaseMessage, IncomingMessage, Sendable},
+    cancellation::CancellationRegistry,
+    errors::ServerError,
+    events::Event,
+    requests::Request,
+    responses::Response,
+    reverse_requests::ReverseRequest,
+    server::{FrameEncoder, FrameProgress, FrameReader, WarningHandler, receive},
+};
+
+/// Handles message encoding and decoding of messages on top of tokio's `AsyncRead` and
+/// `AsyncWrite`.
+///
+/// This is the async counterpart of [`Server`](crate::server::Server) and decodes the wire
+/// format the same way. Available with the `tokio` feature.
+pub struct AsyncServer<R: AsyncRead + Unpin, W: AsyncWrite + Unpin> {
+    input_buffer: BufReader<R>,
+    cancellations: CancellationRegistry,
+    warning_handler: Option<WarningHandler>,
+    frames: FrameReader,
+
+    /// A sharable `AsyncServerOutput` object for sending messages and events from
+    /// other tasks, also while [`AsyncServer::poll_request`] is waiting for input.
+    pub output: Arc<Mutex<AsyncServerOutput<W>>>,
+}
+
+/// Handles emission of messages through the connection of an [`AsyncServer`].
+///
+/// Like [`ServerOutput`](crate::server::ServerOutput), it is only accessible through a mutex
+/// that can be shared with other tasks.
+pub struct AsyncServerOutput<W: AsyncWrite + Unpin> {
+    output_buffer: BufWriter<W>,
+    sequence_number: i64,
+    encoder: FrameEncoder,
+}
+
+impl<R: AsyncRead + Unpin, W: AsyncWrite + Unpin> AsyncServer<R, W> {
+    /// Construct a new AsyncServer using the given input and output streams.
+    pub fn new(input: BufReader<R>, output: BufWriter<W>) -> Self {
+        Self {
+            input_buffer: input,
+            cancellations: CancellationRegistry::new(),
+            warning_handler: None,
+            frames: FrameReader::default(),
+            output: Arc::new(Mutex::new(AsyncServerOutput {
+                output_buffer: output,
+                sequence_number: 0,
+                encoder: FrameEncoder::default(),
+            })),
+        }
+    }
+
+    /// Install a hook that is told about protocol irregularities the server recovered from, see
+    /// [`Server::set_warning_handler`](crate::server::Server::set_warning_handler).
+    pub fn set_warning_handler(&mut self, handler: WarningHandler) {
+        self.warning_handler = Some(handler);
+    }
+
+    /// Whether blank lines before the first header of a message are skipped, see
+    /// [`Server::skip_leading_blanks`](crate::server::Server::skip_leading_blanks).
+    pub fn skip_leading_blanks(&mut self, skip: bool) {
+        self.frames.skip_leading_blanks = skip;
+    }
+
+    /// Whether unknown headers are rejected, see
+    /// [`Server::strict_headers`](crate::server::Server::strict_headers).
+    pub fn strict_headers(&mut self, strict: bool) {
+        self.frames.set_strict_headers(strict);
+    }
+
+    /// Limit the `Content-Length` of incoming messages, see
+    /// [`Server::max_message_size`](crate::server::Server::max_message_size).
+    pub fn max_message_size(&mut self, limit: usize) {
+        self.frames.set_max_message_size(limit);
+    }
+
+    /// The registry of cancelled requests, see
+    /// [`Server::cancellations`](crate::server::Server::cancellations).
+    pub fn cancellations(&self) -> CancellationRegistry {
+        self.cancellations.clone()
+    }
+
+    /// Wait for a request from the development tool.
+    ///
+    /// Returns `Ok(None)` once the input reaches EOF, exactly like
+    /// [`Server::poll_request`](crate::server::Server::poll_request).
+    pub async fn poll_request(&mut self) -> Result<Option<Request>, ServerError> {
+        match self.poll_message().await? {
+            None => Ok(None),
+            Some(IncomingMessage::Request(request)) => Ok(Some(request)),
+            Some(IncomingMessage::Response(_)) => Err(ServerError::UnexpectedMessage {
+                expected: "request".to_string(),
+                found: "response".to_string(),
+            }),
+        }
+    }
+
+    /// Wait for a request or a response to a reverse request, see
+    /// [`Server::poll_message`](crate::server::Server::poll_message).
+    pub async fn poll_message(&mut self) -> Result<Option<IncomingMessage>, ServerError> {
+        let content = loop {
+            let available = self
+                .input_buffer
+                .fill_buf()
+                .await
+                .map_err(ServerError::IoError)?;
+            if available.is_empty() {
+                self.frames.finish()?;
+                return Ok(None); // EOF
+            }
+            let (used, progress) = self.frames.advance(available);
+            self.input_buffer.consume(used);
+            match progress? {
+                FrameProgress::Pending => {}
+                FrameProgress::SkippedBlank => {
+                    if let Some(warn) = &self.warning_handler {
+                        warn("skipped blank lines before a message header");
+                    }
+                }
+                FrameProgress::Frame(content) => break content,
+            }
+        };
+        let message = receive(&content, &self.cancellations)?;
+        Ok(Some(message))
+    }
+
+    pub async fn send(&mut self, body: Sendable) -> Result<(), ServerError> {
+        self.output.lock().await.send(body).await
+    }
+
+    pub async fn respond(&mut self, response: Response) -> Result<(), ServerError> {
+        self.send(Sendable::Response(response)).await
+    }
+
+    pub async fn send_event(&mut self, event: Event) -> Result<(), ServerError> {
+        self.send(Sendable::Event(event)).await
+    }
+
+    /// Send a reverse request and return the `seq` assigned to it.
+    pub async fn send_reverse_request(
+        &mut self,
+        request: ReverseRequest,
+    ) -> Result<i64, ServerError> {
+        self.output.lock().await.send_reverse_request(request).await
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncServerOutput<W> {
+    pub async fn send(&mut self, body: Sendable) -> Result<(), ServerError> {
+        self.sequence_number += 1;
+        let message = BaseMessage {
+            seq: self.sequence_number,
+            message: body,
+        };
+        let (header, content) = self.encoder.encode(&message)?;
+        #[cfg(feature = "tracing")]
+        crate::server::trace_sent(&message, content);
+
+        self.output_buffer
+            .write_all(header.as_bytes())
+            .await
+            .map_err(ServerError::IoError)?;
+        self.output_buffer
+            .write_all(content)
+            .await
+            .map_err(ServerError::IoError)?;
+        self.encoder.trim();
+        self.output_buffer
+            .flush()
+            .await
+            .map_err(ServerError::IoError)
+    }
+
+    pub async fn respond(&mut self, response: Response) -> Result<(), ServerError> {
+        self.send(Sendable::Response(response)).await
+    }
+
+    pub async fn send_event(&mut self, event: Event) -> Result<(), ServerError> {
+        self.send(Sendable::Event(event)).await
+    }
+
+    /// Send a reverse request and return the `seq` assigned to it.
+    pub async fn send_reverse_request(
+        &mut self,
+        request: ReverseRequest,
+    ) -> Result<i64, ServerError> {
+        self.send(Sendable::ReverseRequest(request)).await?;
+        Ok(self.sequence_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::requests::Command;
+    use crate::responses::{ResponseBody, ThreadsResponse};
+
+    fn frame(json: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", json.len(), json)
+    }
+
+    #[tokio::test]
+    async fn test_async_server_poll_and_respond() {
+        let input = frame(r#"{"seq": 1,"type": "request","command": "threads"}"#)
+            + "\n"
+            + &frame(
+                r#"{"seq": 2,"type": "request","command": "cancel","arguments": {"requestId": 1}}"#,
+            );
+        let mut server =
+            AsyncServer::new(BufReader::new(input.as_bytes()), BufWriter::new(Vec::new()));
+
+        let req = server.poll_request().await.unwrap().unwrap();
+        assert!(matches!(req.command, Command::Threads));
+        server
+            .respond(req.success(ResponseBody::Threads(ThreadsResponse { threads: vec![] })))
+            .await
+            .unwrap();
+
+        let req = server.poll_request().await.unwrap().unwrap();
+        assert!(matches!(req.command, Command::Cancel(_)));
+        assert!(server.cancellations().is_cancelled(1));
+        assert!(server.poll_request().await.unwrap().is_none());
+
+        let output = server.output.lock().await;
+        let written = String::from_utf8(output.output_buffer.get_ref().clone()).unwrap();
+        assert!(written.starts_with("Content-Length: "));
+        assert!(written.contains(r#""seq":1"#));
+        assert!(written.contains(r#""command":"threads""#));
+    }
+
+    #[tokio::test]
+    async fn test_async_server_events_while_polling() {
+        let (mut client, server_in) = tokio::io::duplex(1024);
+        let mut server = AsyncServer::new(BufReader::new(server_in), BufWriter::new(Vec::new()));
+        let output = server.output.clone();
+
+        let poll = tokio::spawn(async move {
+            let request = server.poll_request().await;
+            (server, request)
+        });
+        // The reader is waiting for input, events can still be sent
+        output
+            .lock()
+            .await
+            .send_event(Event::Initialized)
+            .await
+            .unwrap();
+        client
+            .write_all(frame(r#"{"seq": 1,"type": "request","command": "threads"}"#).as_bytes())
+            .await
+            .unwrap();
+        drop(client);
+
+        let (mut server, request) = poll.await.unwrap();
+        assert!(matches!(
+            request.unwrap().unwrap().command,
+            Command::Threads
+        ));
+        assert!(server.poll_request().await.unwrap().is_none());
+        let output = output.lock().await;
+        let written = String::from_utf8(output.output_buffer.get_ref().clone()).unwrap();
+        assert!(written.contains(r#""event":"initialized""#));
+    }
+
+    #[tokio::test]
+    async fn test_async_server_reads_like_server() {
+        let threads = frame(r#"{"seq": 1,"type": "request","command": "threads"}"#);
+        let input = "\r\n".to_string() + &threads;
+        let mut server =
+            AsyncServer::new(BufReader::new(input.as_bytes()), BufWriter::new(Vec::new()));
+        let warnings = Arc::new(std::sync::Mutex::new(0));
+        let sink = Arc::clone(&warnings);
+        server.set_warning_handler(Box::new(move |_| *sink.lock().unwrap() += 1));
+        assert!(server.poll_request().await.unwrap().is_some());
+        assert_eq!(*warnings.lock().unwrap(), 1);
+
+        // Header bytes that are not UTF-8 are a header error, not an I/O error
+        let mut input = b"Content-Length: 2\xff\r\n\r\n{}".to_vec();
+        input.extend_from_slice(threads.as_bytes());
+        let mut server = AsyncServer::new(BufReader::new(&input[..]), BufWriter::new(Vec::new()));
+        assert!(matches!(
+            server.poll_request().await,
+            Err(ServerError::HeaderParseError { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_async_server_header_options() {
+        let threads = frame(r#"{"seq": 1,"type": "request","command": "threads"}"#);
+        let input = "\r\n".to_string() + &threads;
+        let mut server =
+            AsyncServer::new(BufReader::new(input.as_bytes()), BufWriter::new(Vec::new()));
+        server.skip_leading_blanks(false);
+        let err = server.poll_request().await.unwrap_err();
+        assert!(matches!(err, ServerError::ProtocolError { .. }));
+
+        let input = "X-Trace: 1\r\n".to_string() + &threads;
+        let mut server =
+            AsyncServer::new(BufReader::new(input.as_bytes()), BufWriter::new(Vec::new()));
+        server.strict_headers(true);
+        let err = server.poll_request().await.unwrap_err();
+        assert!(matches!(err, ServerError::UnknownHeader { header } if header == "X-Trace"));
+
+        let mut server = AsyncServer::new(
+            BufReader::new(threads.as_bytes()),
+            BufWriter::new(Vec::new()),
+        );
+        server.max_message_size(8);
+        let err = server.poll_request().await.unwrap_err();
+        assert!(matches!(err, ServerError::MessageTooLarge { limit: 8, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_async_server_truncated_large_body() {
+        // A body far larger than what arrives must fail without allocating its full length
+        let input = "Content-Length: 1000000\r\n\r\n{\"seq\": 1";
+        let mut server =
+            AsyncServer::new(BufReader::new(input.as_bytes()), BufWriter::new(Vec::new()));
+        let err = server.poll_request().await.unwrap_err();
+        assert!(
+            matches!(err, ServerError::IoError(e) if e.kind() == std::io::ErrorKind::UnexpectedEof)
+        );
+    }
+}
@@ -1,5 +1,13 @@
-use crate::{events::Event, responses::Response, reverse_requests::ReverseRequest};
+use crate::{
+    errors::ValidationError,
+    events::Event,
+    requests::Request,
+    responses::Response,
+    reverse_requests::{ReverseCommand, ReverseRequest},
+    types::StoppedEventReason,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 /// Represents the base protocol message, in which all other messages are wrapped.
 ///
@@ -20,9 +28,92 @@ pub struct BaseMessage {
 pub enum Sendable {
     Response(Response),
     Event(Event),
+    /// Reverse requests are ordinary requests on the wire.
+    #[serde(rename = "request")]
     ReverseRequest(ReverseRequest),
 }
 
+impl Sendable {
+    /// Check the invariants of the message that its types don't encode, e.g. that a failed
+    /// response carries no body or that a stopped event names its thread.
+    ///
+    /// Meant for catching adapter bugs early; see `Server::set_validate_messages` for running it on
+    /// every message sent.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match self {
+            Sendable::Response(response) => validate_response(response),
+            Sendable::Event(event) => validate_event(event),
+            Sendable::ReverseRequest(request) => match &request.command {
+                ReverseCommand::RunInTerminal(arguments) if arguments.args.is_empty() => {
+                    Err(ValidationError::RunInTerminalWithoutArgs)
+                }
+                _ => Ok(()),
+            },
+        }
+    }
+}
+
+fn validate_response(response: &Response) -> Result<(), ValidationError> {
+    if response.success {
+        if response.body.is_none() {
+            return Err(ValidationError::SuccessfulResponseWithoutBody);
+        }
+        if response.message.is_some() {
+            return Err(ValidationError::SuccessfulResponseWithMessage);
+        }
+    } else if let Some(body) = &response.body {
+        // Every body names its command, only some carry an actual `body`. The one of an
+        // error response (see `ErrorResponseBody`) holds nothing but the structured `error`.
+        let carries_body = serde_json::to_value(body)
+            .ok()
+            .and_then(|value| {
+                value.get("body").map(|body| match body {
+                    Value::Null => false,
+                    Value::Object(fields) => fields.keys().any(|key| key != "error"),
+                    _ => true,
+                })
+            })
+            .unwrap_or(false);
+        if carries_body {
+            return Err(ValidationError::FailedResponseWithBody);
+        }
+    }
+    Ok(())
+}
+
+fn validate_event(event: &Event) -> Result<(), ValidationError> {
+    let percentage = match event {
+        Event::Stopped(body) => {
+            if body.thread_id.is_none() && body.all_threads_stopped != Some(true) {
+                return Err(ValidationError::StoppedWithoutThread);
+            }
+            if body.text.is_some() && !matches!(body.reason, StoppedEventReason::Exception) {
+                return Err(ValidationError::StoppedTextWithoutException);
+            }
+            None
+        }
+        Event::ProgressStart(body) => body.percentage,
+        Event::ProgressUpdate(body) => body.percentage,
+        _ => None,
+    };
+    match percentage {
+        Some(percentage) if !(0..=100).contains(&percentage) => {
+            Err(ValidationError::ProgressPercentageOutOfRange { percentage })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A message received from the client.
+///
+/// Besides requests, a client sends responses to the reverse requests (such as `runInTerminal`)
+/// the adapter sent to it.
+#[derive(Debug, Clone)]
+pub enum IncomingMessage {
+    Request(Request),
+    Response(Response),
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -38,4 +129,105 @@ mod tests {
         let expected = "{\"seq\":10,\"type\":\"event\",\"event\":\"initialized\"}";
         assert_eq!(json, expected);
     }
+
+    #[test]
+    fn test_validate() {
+        use crate::{
+            events::{ProgressUpdateEventBody, StoppedEventBody},
+            requests::Command,
+            responses::{ResponseBody, ThreadsResponse},
+            reverse_requests::RunInTerminalRequestArguments,
+            types::Message,
+        };
+
+        let request = Request {
+            seq: 3,
+            command: Command::Threads,
+        };
+        let threads = || ResponseBody::Threads(ThreadsResponse { threads: vec![] });
+        let validate = |response: Response| Sendable::Response(response).validate();
+        assert_eq!(validate(request.clone().success(threads())), Ok(()));
+        assert_eq!(
+            validate(Response::error_for(&request, "no threads")),
+            Ok(())
+        );
+        assert_eq!(validate(request.clone().error("no threads")), Ok(()));
+        assert_eq!(
+            validate(Response::error_with(
+                &request,
+                Message::new(3, "no threads")
+            )),
+            Ok(())
+        );
+        assert_eq!(
+            validate(Response::error_with(&request, Message::default())),
+            Ok(())
+        );
+        assert_eq!(
+            validate(Response {
+                success: false,
+                ..request.clone().success(threads())
+            }),
+            Err(ValidationError::FailedResponseWithBody)
+        );
+        assert_eq!(
+            validate(Response {
+                success: true,
+                ..request.clone().error("no threads")
+            }),
+            Err(ValidationError::SuccessfulResponseWithoutBody)
+        );
+        assert_eq!(
+            validate(Response {
+                message: Some(crate::responses::ResponseMessage::Cancelled),
+                ..request.success(threads())
+            }),
+            Err(ValidationError::SuccessfulResponseWithMessage)
+        );
+
+        let validate = |event: Event| Sendable::Event(event).validate();
+        assert_eq!(
+            validate(Event::stopped(StoppedEventReason::Pause, Some(1))),
+            Ok(())
+        );
+        assert_eq!(
+            validate(Event::stopped(StoppedEventReason::Pause, None)),
+            Err(ValidationError::StoppedWithoutThread)
+        );
+        assert_eq!(
+            validate(Event::Stopped(StoppedEventBody {
+                all_threads_stopped: Some(true),
+                ..StoppedEventBody::new(StoppedEventReason::Pause)
+            })),
+            Ok(())
+        );
+        assert_eq!(
+            validate(Event::Stopped(
+                StoppedEventBody::new(StoppedEventReason::Step)
+                    .with_text("boom")
+                    .with_thread_id(1)
+            )),
+            Err(ValidationError::StoppedTextWithoutException)
+        );
+        assert_eq!(
+            validate(Event::ProgressUpdate(ProgressUpdateEventBody {
+                progress_id: "load".to_string(),
+                percentage: Some(120),
+                ..Default::default()
+            })),
+            Err(ValidationError::ProgressPercentageOutOfRange { percentage: 120 })
+        );
+
+        let run = |args: Vec<String>| {
+            Sendable::ReverseRequest(ReverseRequest {
+                seq: 0,
+                command: ReverseCommand::RunInTerminal(RunInTerminalRequestArguments::new(
+                    "/tmp", args,
+                )),
+            })
+            .validate()
+        };
+        assert_eq!(run(vec!["lua".to_string()]), Ok(()));
+        assert_eq!(run(vec![]), Err(ValidationError::RunInTerminalWithoutArgs));
+    }
 }
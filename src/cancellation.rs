@@ -0,0 +1,62 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Keeps track of requests and progresses the client has cancelled.
+///
+/// The [`Server`](crate::server::Server) records the `requestId` and the `progressId` of every
+/// `cancel` request it reads. Handlers doing slow work (e.g. resolving many breakpoints) on
+/// another thread can poll [`CancellationRegistry::is_cancelled`] with a clone of the registry
+/// and stop early. Clones share the same state.
+#[derive(Debug, Default, Clone)]
+pub struct CancellationRegistry {
+    cancelled: Arc<Mutex<Cancelled>>,
+}
+
+#[derive(Debug, Default)]
+struct Cancelled {
+    requests: HashSet<i64>,
+    progresses: HashSet<String>,
+}
+
+impl CancellationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark the request with the sequence number `request_seq` as cancelled.
+    pub fn cancel(&self, request_seq: i64) {
+        self.lock().requests.insert(request_seq);
+    }
+
+    /// Whether the client cancelled the request with the sequence number `request_seq`.
+    pub fn is_cancelled(&self, request_seq: i64) -> bool {
+        self.lock().requests.contains(&request_seq)
+    }
+
+    /// Forget about a request once it has been answered.
+    pub fn finish(&self, request_seq: i64) {
+        self.lock().requests.remove(&request_seq);
+    }
+
+    /// Mark the progress `progress_id` (announced with a `progressStart` event) as cancelled.
+    pub fn cancel_progress(&self, progress_id: &str) {
+        self.lock().progresses.insert(progress_id.to_string());
+    }
+
+    /// Whether the client cancelled the progress `progress_id`.
+    pub fn is_progress_cancelled(&self, progress_id: &str) -> bool {
+        self.lock().progresses.contains(progress_id)
+    }
+
+    /// Forget about a progress once its `progressEnd` event has been sent.
+    pub fn finish_progress(&self, progress_id: &str) {
+        self.lock().progresses.remove(progress_id);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Cancelled> {
+        // The sets stay consistent even if a holder panicked, so poisoning can be ignored
+        self.cancelled
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,194 @@
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    errors::{DeserializationError, ServerError},
+    events::Event,
+    requests::{Command, Request},
+    responses::{Response, ResponseBody},
+    reverse_requests::ReverseRequest,
+    server::{FrameEncoder, FrameReader, decode_json, parse_error, read_frame},
+};
+
+/// A message received from the debug adapter.
+#[derive(Debug, Clone)]
+pub enum ClientInbound {
+    Response(Response),
+    Event(Event),
+    ReverseRequest(ReverseRequest),
+}
+
+impl ClientInbound {
+    /// The body of a successful response to the request with sequence number `seq`.
+    pub fn response_to(&self, seq: i64) -> Option<&ResponseBody> {
+        match self {
+            ClientInbound::Response(response)
+                if response.request_seq == seq && response.success =>
+            {
+                response.body.as_ref()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The development tool's side of a connection: sends requests to a debug adapter and reads
+/// what it answers.
+///
+/// This is the mirror image of [`Server`](crate::server::Server) and uses the same framing,
+/// e.g. for driving an adapter in integration tests.
+pub struct Client<R: Read, W: Write> {
+    input_buffer: BufReader<R>,
+    output_buffer: BufWriter<W>,
+    sequence_number: i64,
+    frames: FrameReader,
+    encoder: FrameEncoder,
+}
+
+/// A request as it goes over the wire, i.e. with its message `type`.
+#[derive(Serialize)]
+struct OutgoingRequest<'a> {
+    #[serde(rename = "type")]
+    message_type: &'static str,
+    #[serde(flatten)]
+    request: &'a Request,
+}
+
+impl<R: Read, W: Write> Client<R, W> {
+    /// Construct a new Client using the given input and output streams.
+    pub fn new(input: BufReader<R>, output: BufWriter<W>) -> Self {
+        Self {
+            input_buffer: input,
+            output_buffer: output,
+            sequence_number: 0,
+            frames: FrameReader::default(),
+            encoder: FrameEncoder::default(),
+        }
+    }
+
+    /// Send a request for `command` and return the sequence number assigned to it, which the
+    /// adapter's response carries as its `request_seq`.
+    pub fn send_request(&mut self, command: Command) -> Result<i64, ServerError> {
+        self.sequence_number += 1;
+        let request = Request {
+            seq: self.sequence_number,
+            command,
+        };
+        let (header, content) = self.encoder.encode(&OutgoingRequest {
+            message_type: "request",
+            request: &request,
+        })?;
+        self.output_buffer
+            .write_all(header.as_bytes())
+            .and_then(|()| self.output_buffer.write_all(content))
+            .map_err(ServerError::IoError)?;
+        self.encoder.trim();
+        self.output_buffer.flush().map_err(ServerError::IoError)?;
+        Ok(request.seq)
+    }
+
+    /// Wait for a response, event or reverse request from the debug adapter.
+    ///
+    /// Returns `Ok(None)` once the input reaches EOF.
+    pub fn poll_event_or_response(&mut self) -> Result<Option<ClientInbound>, ServerError> {
+        let Some(content) = read_frame(&mut self.input_buffer, &mut self.frames, || {})? else {
+            return Ok(None);
+        };
+        decode_inbound(&content).map(Some)
+    }
+}
+
+fn decode_inbound(content: &[u8]) -> Result<ClientInbound, ServerError> {
+    let value = decode_json(content)?;
+    let invalid = |e| parse_error(content, DeserializationError::SerdeError(e));
+    match value.get("type").and_then(Value::as_str) {
+        Some("response") => serde_json::from_value(value)
+            .map(ClientInbound::Response)
+            .map_err(invalid),
+        Some("event") => serde_json::from_value(value)
+            .map(ClientInbound::Event)
+            .map_err(invalid),
+        Some("request") => serde_json::from_value(value)
+            .map(ClientInbound::ReverseRequest)
+            .map_err(invalid),
+        found => Err(ServerError::UnexpectedMessage {
+            expected: "response, event or request".to_string(),
+            found: found.unwrap_or("untyped").to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        responses::ThreadsResponse, reverse_requests::ReverseCommand, server::Server, types::Thread,
+    };
+
+    #[test]
+    fn test_client_round_trip() {
+        let mut client = Client::new(
+            BufReader::new(Cursor::new(Vec::new())),
+            BufWriter::new(Vec::new()),
+        );
+        let seq = client.send_request(Command::Threads).unwrap();
+        assert_eq!(seq, 1);
+        let sent = client.output_buffer.get_ref().clone();
+
+        // Let an adapter read the request and answer it
+        let mut server_in = Cursor::new(sent);
+        let mut answered = Vec::new();
+        let mut server = Server::new(
+            BufReader::new(&mut server_in),
+            BufWriter::new(&mut answered),
+        );
+        let req = server.poll_request().unwrap().unwrap();
+        assert_eq!(req.seq, 1);
+        assert!(matches!(req.command, Command::Threads));
+        server.send_event(Event::Initialized).unwrap();
+        server
+            .respond(req.success(ResponseBody::Threads(ThreadsResponse {
+                threads: vec![Thread {
+                    id: 1,
+                    name: "main".to_string(),
+                }],
+            })))
+            .unwrap();
+        drop(server);
+
+        let mut client = Client::new(
+            BufReader::new(Cursor::new(answered)),
+            BufWriter::new(Vec::new()),
+        );
+        assert!(matches!(
+            client.poll_event_or_response().unwrap(),
+            Some(ClientInbound::Event(Event::Initialized))
+        ));
+        let inbound = client.poll_event_or_response().unwrap().unwrap();
+        assert!(matches!(
+            inbound.response_to(seq),
+            Some(ResponseBody::Threads(ThreadsResponse { threads })) if threads.len() == 1
+        ));
+        assert!(client.poll_event_or_response().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_client_reads_reverse_request() {
+        let json = r#"{"seq": 3, "type": "request", "command": "runInTerminal", "arguments": {"cwd": "/tmp", "args": ["lua"]}}"#;
+        let input = format!("Content-Length: {}\r\n\r\n{}", json.len(), json);
+        let mut client = Client::new(
+            BufReader::new(Cursor::new(input.into_bytes())),
+            BufWriter::new(Vec::new()),
+        );
+        let Some(ClientInbound::ReverseRequest(request)) = client.poll_event_or_response().unwrap()
+        else {
+            panic!("expected a reverse request");
+        };
+        assert_eq!(request.seq, 3);
+        assert!(matches!(request.command, ReverseCommand::RunInTerminal(_)));
+    }
+}
@@ -1,4 +1,5 @@
-use std::fmt::Debug;
+use alloc::string::String;
+use core::fmt::Debug;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -8,19 +9,67 @@ pub enum DeserializationError {
     #[error("Error while deserializing")]
     SerdeError(#[from] serde_json::Error),
     #[error("Error decoding character stream")]
-    DecodingError(std::str::Utf8Error),
+    DecodingError(core::str::Utf8Error),
+}
+
+/// A cross-field invariant of a message that its types cannot express, see
+/// [`Sendable::validate`](crate::base_message::Sendable::validate), or an invalid value passed
+/// to one of the validating builders of the protocol types.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("A failed response must not carry a body")]
+    FailedResponseWithBody,
+
+    #[error("A successful response must carry the body of its command")]
+    SuccessfulResponseWithoutBody,
+
+    #[error("Only failed responses carry a message")]
+    SuccessfulResponseWithMessage,
+
+    #[error("A stopped event must name its thread unless all threads stopped")]
+    StoppedWithoutThread,
+
+    #[error("Only exception stops carry a text")]
+    StoppedTextWithoutException,
+
+    #[error("Progress percentage {percentage} is outside of 0 to 100")]
+    ProgressPercentageOutOfRange { percentage: i64 },
+
+    #[error("A runInTerminal request must name the command to run")]
+    RunInTerminalWithoutArgs,
+
+    #[error("Invalid reference: {reason}")]
+    InvalidReference { reason: String },
+
+    #[error("Pointer size must be 4 or 8 bytes, not {bytes}")]
+    InvalidPointerSize { bytes: u8 },
+
+    #[error("Invalid range: {reason}")]
+    InvalidRange { reason: String },
 }
 
 #[derive(Debug, Error)]
 pub enum ServerError {
+    #[cfg(feature = "std")]
     #[error("I/O error")]
     IoError(std::io::Error),
 
     #[error("Unknown header: {header}")]
     UnknownHeader { header: String },
 
+    #[error("Unsupported charset: {charset}")]
+    UnsupportedCharset { charset: String },
+
+    #[error("Message of {length} bytes exceeds the limit of {limit} bytes")]
+    MessageTooLarge { length: usize, limit: usize },
+
     #[error("Parse error")]
-    ParseError(#[from] DeserializationError),
+    ParseError {
+        /// The content of the message that could not be parsed, for logging it.
+        content: String,
+        #[source]
+        source: DeserializationError,
+    },
 
     #[error("Could not parse header line '{line}'")]
     HeaderParseError { line: String },
@@ -28,6 +77,9 @@ pub enum ServerError {
     #[error("Protocol error while reading line '{line}', reason: '{reason}'")]
     ProtocolError { reason: String, line: String },
 
+    #[error("Expected a {expected} message, received a {found} message")]
+    UnexpectedMessage { expected: String, found: String },
+
     #[error("Serialization error")]
     SerializationError(#[from] serde_json::Error),
 
@@ -36,6 +88,15 @@ pub enum ServerError {
     )]
     ResponseConstructError,
 
+    #[error("Breakpoint id {id} was never allocated")]
+    UnknownBreakpointId { id: i64 },
+
+    #[error("Invalid event: {reason}")]
+    InvalidEvent { reason: String },
+
+    #[error("Invalid message: {0}")]
+    InvalidMessage(#[from] ValidationError),
+
     #[error("Output lock is poisoned")]
     OutputLockError,
 }
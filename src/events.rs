@@ -1,13 +1,16 @@
+use alloc::{string::String, vec, vec::Vec};
+
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::errors::ValidationError;
 use crate::types::{
     Breakpoint, BreakpointEventReason, Capabilities, InvalidatedAreas, LoadedSourceEventReason,
     Module, ModuleEventReason, OutputEventCategory, OutputEventGroup, ProcessEventStartMethod,
     Source, StoppedEventReason, ThreadEventReason,
 };
 
-//// Arguments for a Breakpoint event.
+/// Arguments for a Breakpoint event.
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct BreakpointEventBody {
@@ -19,14 +22,14 @@ pub struct BreakpointEventBody {
     pub breakpoint: Breakpoint,
 }
 
-//// Arguments for a Capabilities event
+/// Arguments for a Capabilities event
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct CapabilitiesEventBody {
     pub capabilities: Capabilities,
 }
 
-//// Arguments for a Continued event.
+/// Arguments for a Continued event.
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ContinuedEventBody {
@@ -37,7 +40,7 @@ pub struct ContinuedEventBody {
     pub all_threads_continued: Option<bool>,
 }
 
-//// Arguments for a Exited event
+/// Arguments for a Exited event
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ExitedEventBody {
@@ -45,7 +48,7 @@ pub struct ExitedEventBody {
     pub exit_code: i64,
 }
 
-//// Arguments for a Invalidated event
+/// Arguments for a Invalidated event
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct InvalidatedEventBody {
@@ -63,7 +66,20 @@ pub struct InvalidatedEventBody {
     pub stack_frame_id: Option<i64>,
 }
 
-//// Arguments for a LoadedSource event.
+impl InvalidatedEventBody {
+    /// Invalidate the variables of the thread `thread_id`, or only those of the stack frame
+    /// `stack_frame_id` of it when given, e.g. after a `setVariable` request changed one of
+    /// them, since values depending on it may have changed as well.
+    pub fn variables(thread_id: i64, stack_frame_id: Option<i64>) -> Self {
+        Self {
+            areas: Some(vec![InvalidatedAreas::Variables]),
+            thread_id: Some(thread_id),
+            stack_frame_id,
+        }
+    }
+}
+
+/// Arguments for a LoadedSource event.
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct LoadedSourceEventBody {
@@ -74,7 +90,7 @@ pub struct LoadedSourceEventBody {
     pub source: Source,
 }
 
-//// Arguments for a Memory event.
+/// Arguments for a Memory event.
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct MemoryEventBody {
@@ -86,7 +102,7 @@ pub struct MemoryEventBody {
     pub count: i64,
 }
 
-//// Arguments for a Module event.
+/// Arguments for a Module event.
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ModuleEventBody {
@@ -98,7 +114,7 @@ pub struct ModuleEventBody {
     pub module: Module,
 }
 
-//// Arguments for an Output event.
+/// Arguments for an Output event.
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct OutputEventBody {
@@ -152,9 +168,38 @@ pub struct OutputEventBody {
     /// Additional data to report. For the `telemetry` category the data is sent
     /// to telemetry, for the other categories the data is shown in JSON format.
     pub data: Option<Value>,
+    /// A reference that allows the client to request the location where the new
+    /// value is declared. For example, if the logged value is function pointer,
+    /// the adapter may be able to look up the function's location. This should
+    /// be present only if the adapter is likely to be able to resolve the
+    /// location.
+    ///
+    /// This reference shares the same lifetime as the `variablesReference`. See
+    /// [Lifetime of Object References](https://microsoft.github.io/debug-adapter-protocol/overview#lifetime-of-objects-references)
+    /// in the Overview section of the specification for details.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location_reference: Option<i64>,
+}
+
+impl OutputEventBody {
+    /// Output of the given `category`.
+    pub fn new(category: OutputEventCategory, output: impl Into<String>) -> Self {
+        Self {
+            category: Some(category),
+            output: output.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Make the output navigable: the client resolves `reference` with the `locations` request
+    /// (see [`LocationRegistry`](crate::locations::LocationRegistry)).
+    pub fn with_location_reference(mut self, reference: i64) -> Self {
+        self.location_reference = Some(reference);
+        self
+    }
 }
 
-//// Arguments for an Process event.
+/// Arguments for an Process event.
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ProcessEventBody {
@@ -179,7 +224,41 @@ pub struct ProcessEventBody {
     pub pointer_size: Option<i64>,
 }
 
-//// Arguments for a ProgressEnd event.
+impl ProcessEventBody {
+    /// A process named `name`, usually the path to its executable.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the system process id of the debugged process.
+    pub fn with_system_process_id(mut self, id: i64) -> Self {
+        self.system_process_id = Some(id);
+        self
+    }
+
+    /// Set how the debugger started debugging the process.
+    pub fn with_start_method(mut self, method: ProcessEventStartMethod) -> Self {
+        self.start_method = Some(method);
+        self
+    }
+
+    /// Set the size of the process' pointers in bytes, which must be 4 or 8.
+    ///
+    /// The protocol transmits the size in bits, so this sends `32` or `64`. Returns
+    /// [`ValidationError::InvalidPointerSize`] for any other size.
+    pub fn with_pointer_size(mut self, bytes: u8) -> Result<Self, ValidationError> {
+        if !matches!(bytes, 4 | 8) {
+            return Err(ValidationError::InvalidPointerSize { bytes });
+        }
+        self.pointer_size = Some(i64::from(bytes) * 8);
+        Ok(self)
+    }
+}
+
+/// Arguments for a ProgressEnd event.
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ProgressEndEventBody {
@@ -190,7 +269,7 @@ pub struct ProgressEndEventBody {
     pub message: Option<String>,
 }
 
-//// Arguments for a ProgressStart event.
+/// Arguments for a ProgressStart event.
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ProgressStartEventBody {
@@ -221,7 +300,7 @@ pub struct ProgressStartEventBody {
     pub percentage: Option<i64>,
 }
 
-//// Arguments for a ProgressUpdate event.
+/// Arguments for a ProgressUpdate event.
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ProgressUpdateEventBody {
@@ -235,7 +314,7 @@ pub struct ProgressUpdateEventBody {
     pub percentage: Option<i64>,
 }
 
-//// Arguments for a Stopped event.
+/// Arguments for a Stopped event.
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StoppedEventBody {
@@ -259,22 +338,81 @@ pub struct StoppedEventBody {
     /// If `allThreadsStopped` is true, a debug adapter can announce that all
     /// threads have stopped.
     /// - The client should use this information to enable that all threads can
-    /// be expanded to access their stacktraces.
+    ///   be expanded to access their stacktraces.
     /// - If the attribute is missing or false, only the thread with the given
-    /// `threadId` can be expanded.
+    ///   `threadId` can be expanded.
     pub all_threads_stopped: Option<bool>,
     /// Ids of the breakpoints that triggered the event. In most cases there is
     /// only a single breakpoint but here are some examples for multiple
     /// breakpoints:
     /// - Different types of breakpoints map to the same location.
     /// - Multiple source breakpoints get collapsed to the same instruction by
-    /// the compiler/runtime.
+    ///   the compiler/runtime.
     /// - Multiple function breakpoints with different function names map to the
-    /// same location.
+    ///   same location.
     pub hit_breakpoint_ids: Option<Vec<i64>>,
 }
 
-//// Arguments for a Terminated event.
+impl StoppedEventBody {
+    /// A stopped event with the given `reason` and no other attributes set.
+    pub fn new(reason: StoppedEventReason) -> Self {
+        Self {
+            reason,
+            description: None,
+            thread_id: None,
+            preserve_focus_hint: None,
+            text: None,
+            all_threads_stopped: None,
+            hit_breakpoint_ids: None,
+        }
+    }
+
+    /// A stop on an exception, with the exception name `text` and the user visible
+    /// `description` (e.g. the exception message) the client shows in its exception popup.
+    pub fn exception(text: impl Into<String>, description: impl Into<String>) -> Self {
+        Self::new(StoppedEventReason::Exception)
+            .with_text(text)
+            .with_description(description)
+    }
+
+    /// Set the thread which was stopped.
+    pub fn with_thread_id(mut self, thread_id: i64) -> Self {
+        self.thread_id = Some(thread_id);
+        self
+    }
+
+    /// Set the full, user visible reason for the event.
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set the additional information shown in the UI, the exception name for `exception`
+    /// stops. [`ServerOutput::send_stopped`](crate::server::ServerOutput::send_stopped) rejects
+    /// it for other reasons.
+    pub fn with_text(mut self, text: impl Into<String>) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    /// Announce that all threads have stopped.
+    pub fn with_all_threads_stopped(mut self, all_threads_stopped: bool) -> Self {
+        self.all_threads_stopped = Some(all_threads_stopped);
+        self
+    }
+
+    /// List the breakpoints that triggered the event, so the client can highlight them.
+    ///
+    /// The ids have to be ones the adapter handed out, see
+    /// [`ServerOutput::allocate_breakpoint_id`](crate::server::ServerOutput::allocate_breakpoint_id).
+    /// [`ServerOutput::send_stopped`](crate::server::ServerOutput::send_stopped) rejects unknown ids.
+    pub fn with_hit_breakpoint_ids(mut self, ids: impl IntoIterator<Item = i64>) -> Self {
+        self.hit_breakpoint_ids = Some(ids.into_iter().collect());
+        self
+    }
+}
+
+/// Arguments for a Terminated event.
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct TerminatedEventBody {
@@ -285,7 +423,7 @@ pub struct TerminatedEventBody {
     pub restart: Option<Value>,
 }
 
-//// Arguments for a Thread event.
+/// Arguments for a Thread event.
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ThreadEventBody {
@@ -405,3 +543,79 @@ pub enum Event {
     /// Specification: [Thread event](https://microsoft.github.io/debug-adapter-protocol/specification#Events_Thread)
     Thread(ThreadEventBody),
 }
+
+impl Event {
+    /// A `stopped` event for `reason`, on the thread `thread_id` if given.
+    ///
+    /// Use [`StoppedEventBody`]'s builders for the other attributes.
+    pub fn stopped(reason: StoppedEventReason, thread_id: Option<i64>) -> Self {
+        let body = StoppedEventBody::new(reason);
+        Event::Stopped(match thread_id {
+            Some(thread_id) => body.with_thread_id(thread_id),
+            None => body,
+        })
+    }
+
+    /// An `output` event with `text` in the given `category`.
+    pub fn output(category: OutputEventCategory, text: impl Into<String>) -> Self {
+        Event::Output(OutputEventBody::new(category, text))
+    }
+
+    /// A `terminated` event that does not ask the client to restart the session.
+    pub fn terminated() -> Self {
+        Event::Terminated(None)
+    }
+
+    /// A `thread` event telling that the thread `thread_id` started or exited.
+    pub fn thread(reason: ThreadEventReason, thread_id: i64) -> Self {
+        Event::Thread(ThreadEventBody { reason, thread_id })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_constructors() {
+        let json = |event: &Event| serde_json::to_value(event).unwrap();
+
+        let stopped = json(&Event::stopped(StoppedEventReason::Breakpoint, Some(1)));
+        assert_eq!(stopped["event"], "stopped");
+        assert_eq!(stopped["body"]["reason"], "breakpoint");
+        assert_eq!(stopped["body"]["threadId"], 1);
+
+        let output = json(&Event::output(OutputEventCategory::Stderr, "oops\n"));
+        assert_eq!(output["body"]["category"], "stderr");
+        assert_eq!(output["body"]["output"], "oops\n");
+
+        assert!(matches!(Event::terminated(), Event::Terminated(None)));
+        assert_eq!(json(&Event::terminated())["event"], "terminated");
+
+        let thread = json(&Event::thread(ThreadEventReason::Exited, 3));
+        assert_eq!(
+            thread["body"],
+            serde_json::json!({ "reason": "exited", "threadId": 3 })
+        );
+    }
+
+    #[test]
+    fn test_process_event_pointer_size() {
+        let body = ProcessEventBody::new("/usr/bin/lua")
+            .with_system_process_id(4711)
+            .with_start_method(ProcessEventStartMethod::Launch)
+            .with_pointer_size(8)
+            .unwrap();
+        let value = serde_json::to_value(Event::Process(body)).unwrap();
+        assert_eq!(value["event"], "process");
+        assert_eq!(value["body"]["name"], "/usr/bin/lua");
+        assert_eq!(value["body"]["systemProcessId"], 4711);
+        assert_eq!(value["body"]["startMethod"], "launch");
+        assert_eq!(value["body"]["pointerSize"], 64);
+
+        assert!(matches!(
+            ProcessEventBody::new("lua").with_pointer_size(2),
+            Err(ValidationError::InvalidPointerSize { bytes: 2 })
+        ));
+    }
+}
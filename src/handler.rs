@@ -0,0 +1,546 @@
+//! Routing of requests to the methods of a [`DapHandler`].
+//!
+//! An adapter implements the methods for the requests it supports and passes every request it
+//! reads to [`dispatch`], which turns the result into the matching response. Requests without
+//! an implementation are answered with an error response.
+
+use alloc::string::ToString;
+
+use serde_json::Value;
+
+use crate::{
+    requests::{
+        AttachRequestArguments, BreakpointLocationsArguments, CancelArguments, Command,
+        CompletionsArguments, ContinueArguments, CustomCommand, DataBreakpointInfoArguments,
+        DisassembleArguments, DisconnectArguments, EvaluateArguments, ExceptionInfoArguments,
+        GotoArguments, GotoTargetsArguments, InitializeArguments, LaunchRequestArguments,
+        LocationsArguments, ModulesArguments, NextArguments, PauseArguments, ReadMemoryArguments,
+        Request, RestartArguments, RestartFrameArguments, ReverseContinueArguments,
+        ScopesArguments, SetBreakpointsArguments, SetDataBreakpointsArguments,
+        SetExceptionBreakpointsArguments, SetExpressionArguments, SetFunctionBreakpointsArguments,
+        SetInstructionBreakpointsArguments, SetVariableArguments, SourceArguments,
+        StackTraceArguments, StepBackArguments, StepInArguments, StepInTargetsArguments,
+        StepOutArguments, TerminateArguments, TerminateThreadsArguments, VariablesArguments,
+        WriteMemoryArguments,
+    },
+    responses::{
+        BreakpointLocationsResponse, CompletionsResponse, ContinueResponse, CustomResponse,
+        DataBreakpointInfoResponse, DisassembleResponse, EvaluateResponse, ExceptionInfoResponse,
+        GotoTargetsResponse, LoadedSourcesResponse, LocationsResponse, ModulesResponse,
+        ReadMemoryResponse, Response, ResponseBody, ScopesResponse, SetBreakpointsResponse,
+        SetDataBreakpointsResponse, SetExceptionBreakpointsResponse, SetExpressionResponse,
+        SetFunctionBreakpointsResponse, SetInstructionBreakpointsResponse, SetVariableResponse,
+        SourceResponse, StackTraceResponse, ThreadsResponse, VariablesResponse,
+        WriteMemoryResponse,
+    },
+    types::{Capabilities, Message},
+};
+
+/// The requests a debug adapter handles, one method per request.
+///
+/// Every method fails with an "unsupported" error by default, so an adapter only implements
+/// the requests it supports. Errors are returned as a [`Message`], which [`dispatch`] sends as
+/// the structured error of the response.
+// Returning the protocol's error shape as is keeps handlers simple; errors are rare enough that
+// the size of `Message` does not matter.
+#[allow(clippy::result_large_err)]
+pub trait DapHandler {
+    /// Handle the `attach` request.
+    fn attach(&mut self, _args: AttachRequestArguments) -> Result<(), Message> {
+        Err(unsupported("attach"))
+    }
+
+    /// Handle the `breakpointLocations` request.
+    fn breakpoint_locations(
+        &mut self,
+        _args: BreakpointLocationsArguments,
+    ) -> Result<BreakpointLocationsResponse, Message> {
+        Err(unsupported("breakpointLocations"))
+    }
+
+    /// Handle the `cancel` request.
+    fn cancel(&mut self, _args: CancelArguments) -> Result<(), Message> {
+        Err(unsupported("cancel"))
+    }
+
+    /// Handle the `completions` request.
+    fn completions(&mut self, _args: CompletionsArguments) -> Result<CompletionsResponse, Message> {
+        Err(unsupported("completions"))
+    }
+
+    /// Handle the `configurationDone` request.
+    fn configuration_done(&mut self) -> Result<(), Message> {
+        Err(unsupported("configurationDone"))
+    }
+
+    /// Handle the `continue` request.
+    fn continue_(&mut self, _args: ContinueArguments) -> Result<ContinueResponse, Message> {
+        Err(unsupported("continue"))
+    }
+
+    /// Handle the `dataBreakpointInfo` request.
+    fn data_breakpoint_info(
+        &mut self,
+        _args: DataBreakpointInfoArguments,
+    ) -> Result<DataBreakpointInfoResponse, Message> {
+        Err(unsupported("dataBreakpointInfo"))
+    }
+
+    /// Handle the `disassemble` request.
+    fn disassemble(&mut self, _args: DisassembleArguments) -> Result<DisassembleResponse, Message> {
+        Err(unsupported("disassemble"))
+    }
+
+    /// Handle the `disconnect` request.
+    fn disconnect(&mut self, _args: DisconnectArguments) -> Result<(), Message> {
+        Err(unsupported("disconnect"))
+    }
+
+    /// Handle the `evaluate` request.
+    fn evaluate(&mut self, _args: EvaluateArguments) -> Result<EvaluateResponse, Message> {
+        Err(unsupported("evaluate"))
+    }
+
+    /// Handle the `exceptionInfo` request.
+    fn exception_info(
+        &mut self,
+        _args: ExceptionInfoArguments,
+    ) -> Result<ExceptionInfoResponse, Message> {
+        Err(unsupported("exceptionInfo"))
+    }
+
+    /// Handle the `goto` request.
+    fn goto(&mut self, _args: GotoArguments) -> Result<(), Message> {
+        Err(unsupported("goto"))
+    }
+
+    /// Handle the `gotoTargets` request.
+    fn goto_targets(
+        &mut self,
+        _args: GotoTargetsArguments,
+    ) -> Result<GotoTargetsResponse, Message> {
+        Err(unsupported("gotoTargets"))
+    }
+
+    /// Handle the `initialize` request.
+    fn initialize(&mut self, _args: InitializeArguments) -> Result<Capabilities, Message> {
+        Err(unsupported("initialize"))
+    }
+
+    /// Handle the `launch` request.
+    fn launch(&mut self, _args: LaunchRequestArguments) -> Result<(), Message> {
+        Err(unsupported("launch"))
+    }
+
+    /// Handle the `loadedSources` request.
+    fn loaded_sources(&mut self) -> Result<LoadedSourcesResponse, Message> {
+        Err(unsupported("loadedSources"))
+    }
+
+    /// Handle the `locations` request.
+    fn locations(&mut self, _args: LocationsArguments) -> Result<LocationsResponse, Message> {
+        Err(unsupported("locations"))
+    }
+
+    /// Handle the `modules` request.
+    fn modules(&mut self, _args: ModulesArguments) -> Result<ModulesResponse, Message> {
+        Err(unsupported("modules"))
+    }
+
+    /// Handle the `next` request.
+    fn next(&mut self, _args: NextArguments) -> Result<(), Message> {
+        Err(unsupported("next"))
+    }
+
+    /// Handle the `pause` request.
+    fn pause(&mut self, _args: PauseArguments) -> Result<(), Message> {
+        Err(unsupported("pause"))
+    }
+
+    /// Handle the `readMemory` request.
+    fn read_memory(&mut self, _args: ReadMemoryArguments) -> Result<ReadMemoryResponse, Message> {
+        Err(unsupported("readMemory"))
+    }
+
+    /// Handle the `restart` request.
+    fn restart(&mut self, _args: RestartArguments) -> Result<(), Message> {
+        Err(unsupported("restart"))
+    }
+
+    /// Handle the `restartFrame` request.
+    fn restart_frame(&mut self, _args: RestartFrameArguments) -> Result<(), Message> {
+        Err(unsupported("restartFrame"))
+    }
+
+    /// Handle the `reverseContinue` request.
+    fn reverse_continue(&mut self, _args: ReverseContinueArguments) -> Result<(), Message> {
+        Err(unsupported("reverseContinue"))
+    }
+
+    /// Handle the `scopes` request.
+    fn scopes(&mut self, _args: ScopesArguments) -> Result<ScopesResponse, Message> {
+        Err(unsupported("scopes"))
+    }
+
+    /// Handle the `setBreakpoints` request.
+    fn set_breakpoints(
+        &mut self,
+        _args: SetBreakpointsArguments,
+    ) -> Result<SetBreakpointsResponse, Message> {
+        Err(unsupported("setBreakpoints"))
+    }
+
+    /// Handle the `setDataBreakpoints` request.
+    fn set_data_breakpoints(
+        &mut self,
+        _args: SetDataBreakpointsArguments,
+    ) -> Result<SetDataBreakpointsResponse, Message> {
+        Err(unsupported("setDataBreakpoints"))
+    }
+
+    /// Handle the `setExceptionBreakpoints` request.
+    fn set_exception_breakpoints(
+        &mut self,
+        _args: SetExceptionBreakpointsArguments,
+    ) -> Result<SetExceptionBreakpointsResponse, Message> {
+        Err(unsupported("setExceptionBreakpoints"))
+    }
+
+    /// Handle the `setExpression` request.
+    fn set_expression(
+        &mut self,
+        _args: SetExpressionArguments,
+    ) -> Result<SetExpressionResponse, Message> {
+        Err(unsupported("setExpression"))
+    }
+
+    /// Handle the `setFunctionBreakpoints` request.
+    fn set_function_breakpoints(
+        &mut self,
+        _args: SetFunctionBreakpointsArguments,
+    ) -> Result<SetFunctionBreakpointsResponse, Message> {
+        Err(unsupported("setFunctionBreakpoints"))
+    }
+
+    /// Handle the `setInstructionBreakpoints` request.
+    fn set_instruction_breakpoints(
+        &mut self,
+        _args: SetInstructionBreakpointsArguments,
+    ) -> Result<SetInstructionBreakpointsResponse, Message> {
+        Err(unsupported("setInstructionBreakpoints"))
+    }
+
+    /// Handle the `setVariable` request.
+    fn set_variable(
+        &mut self,
+        _args: SetVariableArguments,
+    ) -> Result<SetVariableResponse, Message> {
+        Err(unsupported("setVariable"))
+    }
+
+    /// Handle the `source` request.
+    fn source(&mut self, _args: SourceArguments) -> Result<SourceResponse, Message> {
+        Err(unsupported("source"))
+    }
+
+    /// Handle the `stackTrace` request.
+    fn stack_trace(&mut self, _args: StackTraceArguments) -> Result<StackTraceResponse, Message> {
+        Err(unsupported("stackTrace"))
+    }
+
+    /// Handle the `stepBack` request.
+    fn step_back(&mut self, _args: StepBackArguments) -> Result<(), Message> {
+        Err(unsupported("stepBack"))
+    }
+
+    /// Handle the `stepIn` request.
+    fn step_in(&mut self, _args: StepInArguments) -> Result<(), Message> {
+        Err(unsupported("stepIn"))
+    }
+
+    /// Handle the `stepInTargets` request.
+    ///
+    /// This crate does not model the response body, so the handler returns it as JSON.
+    fn step_in_targets(&mut self, _args: StepInTargetsArguments) -> Result<Value, Message> {
+        Err(unsupported("stepInTargets"))
+    }
+
+    /// Handle the `stepOut` request.
+    fn step_out(&mut self, _args: StepOutArguments) -> Result<(), Message> {
+        Err(unsupported("stepOut"))
+    }
+
+    /// Handle the `terminate` request.
+    fn terminate(&mut self, _args: TerminateArguments) -> Result<(), Message> {
+        Err(unsupported("terminate"))
+    }
+
+    /// Handle the `terminateThreads` request.
+    fn terminate_threads(&mut self, _args: TerminateThreadsArguments) -> Result<(), Message> {
+        Err(unsupported("terminateThreads"))
+    }
+
+    /// Handle the `threads` request.
+    fn threads(&mut self) -> Result<ThreadsResponse, Message> {
+        Err(unsupported("threads"))
+    }
+
+    /// Handle the `variables` request.
+    fn variables(&mut self, _args: VariablesArguments) -> Result<VariablesResponse, Message> {
+        Err(unsupported("variables"))
+    }
+
+    /// Handle the `writeMemory` request.
+    fn write_memory(
+        &mut self,
+        _args: WriteMemoryArguments,
+    ) -> Result<WriteMemoryResponse, Message> {
+        Err(unsupported("writeMemory"))
+    }
+
+    /// Handle a request this crate does not know about, returning the raw body of its response.
+    fn custom(&mut self, command: CustomCommand) -> Result<Option<Value>, Message> {
+        Err(unsupported(&command.command))
+    }
+}
+
+/// Call the method of `handler` for `request` and wrap its result into the response to it.
+///
+/// Failures become error responses like [`Response::error_with`] builds them.
+pub fn dispatch(handler: &mut impl DapHandler, request: Request) -> Response {
+    let Request { seq, command } = request;
+    let name = command.name().to_string();
+    let result = match command {
+        Command::Attach(args) => handler.attach(args).map(|()| ResponseBody::Attach),
+        Command::BreakpointLocations(args) => handler
+            .breakpoint_locations(args)
+            .map(ResponseBody::BreakpointLocations),
+        Command::Cancel(args) => handler.cancel(args).map(|()| ResponseBody::Cancel),
+        Command::Completions(args) => handler.completions(args).map(ResponseBody::Completions),
+        Command::ConfigurationDone => handler
+            .configuration_done()
+            .map(|()| ResponseBody::ConfigurationDone),
+        Command::Continue(args) => handler.continue_(args).map(ResponseBody::Continue),
+        Command::DataBreakpointInfo(args) => handler
+            .data_breakpoint_info(args)
+            .map(ResponseBody::DataBreakpointInfo),
+        Command::Disassemble(args) => handler.disassemble(args).map(ResponseBody::Disassemble),
+        Command::Disconnect(args) => handler.disconnect(args).map(|()| ResponseBody::Disconnect),
+        Command::Evaluate(args) => handler.evaluate(args).map(ResponseBody::Evaluate),
+        Command::ExceptionInfo(args) => handler
+            .exception_info(args)
+            .map(ResponseBody::ExceptionInfo),
+        Command::Goto(args) => handler.goto(args).map(|()| ResponseBody::Goto),
+        Command::GotoTargets(args) => handler.goto_targets(args).map(ResponseBody::GotoTargets),
+        Command::Initialize(args) => handler.initialize(args).map(ResponseBody::Initialize),
+        Command::Launch(args) => handler.launch(args).map(|()| ResponseBody::Launch),
+        Command::LoadedSources => handler.loaded_sources().map(ResponseBody::LoadedSources),
+        Command::Locations(args) => handler.locations(args).map(ResponseBody::Locations),
+        Command::Modules(args) => handler.modules(args).map(ResponseBody::Modules),
+        Command::Next(args) => handler.next(args).map(|()| ResponseBody::Next),
+        Command::Pause(args) => handler.pause(args).map(|()| ResponseBody::Pause),
+        Command::ReadMemory(args) => handler.read_memory(args).map(ResponseBody::ReadMemory),
+        Command::Restart(args) => handler.restart(args).map(|()| ResponseBody::Restart),
+        Command::RestartFrame(args) => handler
+            .restart_frame(args)
+            .map(|()| ResponseBody::RestartFrame),
+        Command::ReverseContinue(args) => handler
+            .reverse_continue(args)
+            .map(|()| ResponseBody::ReverseContinue),
+        Command::Scopes(args) => handler.scopes(args).map(ResponseBody::Scopes),
+        Command::SetBreakpoints(args) => handler
+            .set_breakpoints(args)
+            .map(ResponseBody::SetBreakpoints),
+        Command::SetDataBreakpoints(args) => handler
+            .set_data_breakpoints(args)
+            .map(ResponseBody::SetDataBreakpoints),
+        Command::SetExceptionBreakpoints(args) => handler
+            .set_exception_breakpoints(args)
+            .map(ResponseBody::SetExceptionBreakpoints),
+        Command::SetExpression(args) => handler
+            .set_expression(args)
+            .map(ResponseBody::SetExpression),
+        Command::SetFunctionBreakpoints(args) => handler
+            .set_function_breakpoints(args)
+            .map(ResponseBody::SetFunctionBreakpoints),
+        Command::SetInstructionBreakpoints(args) => handler
+            .set_instruction_breakpoints(args)
+            .map(ResponseBody::SetInstructionBreakpoints),
+        Command::SetVariable(args) => handler.set_variable(args).map(ResponseBody::SetVariable),
+        Command::Source(args) => handler.source(args).map(ResponseBody::Source),
+        Command::StackTrace(args) => handler.stack_trace(args).map(ResponseBody::StackTrace),
+        Command::StepBack(args) => handler.step_back(args).map(|()| ResponseBody::StepBack),
+        Command::StepIn(args) => handler.step_in(args).map(|()| ResponseBody::StepIn),
+        Command::StepInTargets(args) => handler
+            .step_in_targets(args)
+            .map(|body| custom_body(&name, Some(body))),
+        Command::StepOut(args) => handler.step_out(args).map(|()| ResponseBody::StepOut),
+        Command::Terminate(args) => handler.terminate(args).map(|()| ResponseBody::Terminate),
+        Command::TerminateThreads(args) => handler
+            .terminate_threads(args)
+            .map(|()| ResponseBody::TerminateThreads),
+        Command::Threads => handler.threads().map(ResponseBody::Threads),
+        Command::Variables(args) => handler.variables(args).map(ResponseBody::Variables),
+        Command::WriteMemory(args) => handler.write_memory(args).map(ResponseBody::WriteMemory),
+        Command::Custom(command) => handler.custom(command).map(|body| custom_body(&name, body)),
+    };
+    match result {
+        Ok(body) => Response {
+            request_seq: seq,
+            success: true,
+            message: None,
+            body: Some(body),
+            error: None,
+        },
+        Err(error) => {
+            let message = error.formatted();
+            Response::structured_error(seq, &name, message, error)
+        }
+    }
+}
+
+/// The error of a request the handler does not implement.
+pub(crate) fn unsupported(command: &str) -> Message {
+    Message::new(
+        0,
+        "The debug adapter does not handle the '{command}' request",
+    )
+    .with_variable("command", command)
+}
+
+/// A body naming `command`, for responses this crate has no body type for.
+fn custom_body(command: &str, body: Option<Value>) -> ResponseBody {
+    ResponseBody::Custom(CustomResponse {
+        command: command.to_string(),
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{responses::ResponseMessage, types::Thread};
+
+    #[derive(Default)]
+    struct Adapter {
+        initialized: bool,
+    }
+
+    #[allow(clippy::result_large_err)]
+    impl DapHandler for Adapter {
+        fn initialize(&mut self, _args: InitializeArguments) -> Result<Capabilities, Message> {
+            self.initialized = true;
+            Ok(Capabilities::default().with_supports_set_expression(true))
+        }
+
+        fn threads(&mut self) -> Result<ThreadsResponse, Message> {
+            Ok(ThreadsResponse {
+                threads: vec![Thread {
+                    id: 1,
+                    name: "main".to_string(),
+                }],
+            })
+        }
+
+        fn cancel(&mut self, _args: CancelArguments) -> Result<(), Message> {
+            Ok(())
+        }
+
+        fn pause(&mut self, args: PauseArguments) -> Result<(), Message> {
+            Err(Message {
+                id: 7,
+                format: "Thread {thread} is not running".to_string(),
+                variables: [("thread".to_string(), args.thread_id.to_string())]
+                    .into_iter()
+                    .collect(),
+                show_user: Some(true),
+                ..Default::default()
+            })
+        }
+    }
+
+    fn request(json: &str) -> Request {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let mut adapter = Adapter::default();
+
+        let response = dispatch(
+            &mut adapter,
+            request(r#"{"seq": 1, "command": "initialize", "arguments": {"adapterID": "lua"}}"#),
+        );
+        assert!(adapter.initialized);
+        assert_eq!(response.request_seq, 1);
+        assert!(response.success);
+        assert!(matches!(
+            response.body,
+            Some(ResponseBody::Initialize(Capabilities {
+                supports_set_expression: Some(true),
+                ..
+            }))
+        ));
+
+        let response = dispatch(&mut adapter, request(r#"{"seq": 2, "command": "threads"}"#));
+        assert!(matches!(
+            response.body,
+            Some(ResponseBody::Threads(ThreadsResponse { threads })) if threads.len() == 1
+        ));
+
+        let response = dispatch(
+            &mut adapter,
+            request(r#"{"seq": 4, "command": "cancel", "arguments": {"requestId": 2}}"#),
+        );
+        assert!(matches!(response.body, Some(ResponseBody::Cancel)));
+        assert_eq!(
+            serde_json::to_value(&response).unwrap()["command"],
+            "cancel"
+        );
+
+        // Failures keep the structured error and name the command
+        let response = dispatch(
+            &mut adapter,
+            request(r#"{"seq": 3, "command": "pause", "arguments": {"threadId": 4}}"#),
+        );
+        assert!(!response.success);
+        assert!(matches!(
+            &response.message,
+            Some(ResponseMessage::Error(message)) if message == "Thread 4 is not running"
+        ));
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value["body"]["error"]["id"], 7);
+        assert_eq!(value["request_seq"], 3);
+        assert_eq!(value["command"], "pause");
+    }
+
+    #[test]
+    fn test_dispatch_unimplemented() {
+        let mut adapter = Adapter::default();
+
+        let response = dispatch(
+            &mut adapter,
+            request(r#"{"seq": 5, "command": "stepBack", "arguments": {"threadId": 1}}"#),
+        );
+        assert_eq!(response.request_seq, 5);
+        assert!(!response.success);
+        assert!(matches!(
+            &response.message,
+            Some(ResponseMessage::Error(message))
+                if message == "The debug adapter does not handle the 'stepBack' request"
+        ));
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value["command"], "stepBack");
+        assert_eq!(value["body"]["error"]["variables"]["command"], "stepBack");
+
+        let response = dispatch(
+            &mut adapter,
+            request(r#"{"seq": 6, "command": "emmy/profile"}"#),
+        );
+        assert!(!response.success);
+        assert_eq!(
+            serde_json::to_value(&response).unwrap()["command"],
+            "emmy/profile"
+        );
+    }
+}
@@ -15,14 +15,33 @@
 //! the output it receives (this is why it's called an "adapter" - it adapts the debugger to
 //! editors that know DAP).
 //!
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "tokio")]
+pub mod async_server;
 pub mod base_message;
+#[cfg(feature = "std")]
+pub mod cancellation;
+#[cfg(feature = "std")]
+pub mod client;
 pub mod errors;
 pub mod events;
+pub mod handler;
+pub mod loaded_sources;
+#[cfg(feature = "std")]
+pub mod locations;
+#[cfg(feature = "std")]
+pub mod modules;
 pub mod prelude;
 pub mod requests;
 pub mod responses;
 pub mod reverse_requests;
+#[cfg(feature = "std")]
 pub mod server;
+#[cfg(feature = "std")]
+pub mod shutdown;
 pub mod types;
 pub mod utils;
 pub use utils::get_spec_version;
@@ -0,0 +1,112 @@
+use alloc::vec::Vec;
+
+use crate::{
+    events::{Event, LoadedSourceEventBody},
+    responses::LoadedSourcesResponse,
+    types::{LoadedSourceEventReason, Source},
+};
+
+/// The set of sources the debuggee has loaded, for answering the `loadedSources` request and
+/// keeping the client up to date through `loadedSource` events.
+///
+/// Sources are identified by their `sourceReference` if they have one, otherwise by their path.
+#[derive(Debug, Default, Clone)]
+pub struct LoadedSources {
+    sources: Vec<Source>,
+}
+
+impl LoadedSources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a loaded source and return the `loadedSource` event announcing it: `new` for a
+    /// source that was not known yet, `changed` for one that replaces a known source.
+    pub fn add(&mut self, source: Source) -> Event {
+        let reason = match self.position(&source) {
+            Some(index) => {
+                self.sources[index] = source.clone();
+                LoadedSourceEventReason::Changed
+            }
+            None => {
+                self.sources.push(source.clone());
+                LoadedSourceEventReason::New
+            }
+        };
+        Event::LoadedSource(LoadedSourceEventBody { reason, source })
+    }
+
+    /// Forget a source, returning the `removed` event if it was known.
+    pub fn remove(&mut self, source: &Source) -> Option<Event> {
+        let index = self.position(source)?;
+        let source = self.sources.remove(index);
+        Some(Event::LoadedSource(LoadedSourceEventBody {
+            reason: LoadedSourceEventReason::Removed,
+            source,
+        }))
+    }
+
+    /// The loaded sources, in the order they were first added.
+    pub fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+
+    /// The body of the response to a `loadedSources` request.
+    pub fn response(&self) -> LoadedSourcesResponse {
+        LoadedSourcesResponse {
+            sources: self.sources.clone(),
+        }
+    }
+
+    fn position(&self, source: &Source) -> Option<usize> {
+        self.sources
+            .iter()
+            .position(|known| same_source(known, source))
+    }
+}
+
+fn same_source(a: &Source, b: &Source) -> bool {
+    match (a.source_reference, b.source_reference) {
+        (Some(a), Some(b)) if a > 0 && b > 0 => a == b,
+        _ => a.path.is_some() && a.path == b.path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(path: &str) -> Source {
+        Source {
+            path: Some(path.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_loaded_sources() {
+        let mut loaded = LoadedSources::new();
+        let event = loaded.add(source("/game/main.lua"));
+        assert!(matches!(
+            event,
+            Event::LoadedSource(LoadedSourceEventBody {
+                reason: LoadedSourceEventReason::New,
+                ..
+            })
+        ));
+        loaded.add(source("/game/ui.lua"));
+        let event = loaded.add(source("/game/main.lua"));
+        assert!(matches!(
+            event,
+            Event::LoadedSource(LoadedSourceEventBody {
+                reason: LoadedSourceEventReason::Changed,
+                ..
+            })
+        ));
+        assert_eq!(loaded.response().sources.len(), 2);
+
+        assert!(loaded.remove(&source("/game/main.lua")).is_some());
+        assert!(loaded.remove(&source("/game/main.lua")).is_none());
+        assert_eq!(loaded.sources()[0].path.as_deref(), Some("/game/ui.lua"));
+    }
+}
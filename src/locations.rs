@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use crate::{requests::LocationsArguments, responses::LocationsResponse};
+
+/// Allocates location references and resolves them for the `locations` request.
+///
+/// Location references (e.g. `Variable::declaration_location_reference`) share the lifetime of
+/// the `variablesReference` they were returned alongside, so adapters typically call
+/// [`LocationRegistry::clear`] whenever execution resumes.
+#[derive(Debug, Default, Clone)]
+pub struct LocationRegistry {
+    next_reference: i64,
+    locations: HashMap<i64, LocationsResponse>,
+}
+
+impl LocationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store `location` and return the reference that identifies it. References start at 1 and
+    /// are never reused within the lifetime of the registry.
+    pub fn register(&mut self, location: LocationsResponse) -> i64 {
+        self.next_reference += 1;
+        self.locations.insert(self.next_reference, location);
+        self.next_reference
+    }
+
+    /// Look up a previously registered location.
+    pub fn resolve(&self, reference: i64) -> Option<&LocationsResponse> {
+        self.locations.get(&reference)
+    }
+
+    /// Look up the location requested by a `locations` request.
+    pub fn resolve_arguments(&self, args: &LocationsArguments) -> Option<&LocationsResponse> {
+        self.resolve(args.location_reference)
+    }
+
+    /// Forget all registered locations, e.g. when the debuggee resumes execution.
+    pub fn clear(&mut self) {
+        self.locations.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{Event, OutputEventBody};
+    use crate::types::{OutputEventCategory, Source, Variable};
+
+    #[test]
+    fn test_variable_declaration_location() {
+        let mut registry = LocationRegistry::new();
+        let reference = registry.register(LocationsResponse {
+            source: Source {
+                path: Some("/src/game.lua".to_string()),
+                ..Default::default()
+            },
+            line: 12,
+            column: Some(7),
+            ..Default::default()
+        });
+
+        let variable =
+            Variable::new("player", "table: 0x5581").with_declaration_location_reference(reference);
+        let val = serde_json::to_value(&variable).unwrap();
+        assert_eq!(val["declarationLocationReference"], reference);
+
+        let location = registry
+            .resolve_arguments(&LocationsArguments {
+                location_reference: reference,
+            })
+            .unwrap();
+        assert_eq!(location.line, 12);
+        assert_eq!(location.source.path.as_deref(), Some("/src/game.lua"));
+
+        registry.clear();
+        assert!(registry.resolve(reference).is_none());
+    }
+
+    #[test]
+    fn test_output_location_reference() {
+        let mut registry = LocationRegistry::new();
+        let reference = registry.register(LocationsResponse {
+            line: 88,
+            ..Default::default()
+        });
+
+        let output =
+            OutputEventBody::new(OutputEventCategory::Stderr, "  at update (game.lua:88)\n")
+                .with_location_reference(reference);
+        let val = serde_json::to_value(Event::Output(output)).unwrap();
+        assert_eq!(val["event"], "output");
+        assert_eq!(val["body"]["locationReference"], reference);
+        assert_eq!(registry.resolve(reference).unwrap().line, 88);
+    }
+}
@@ -0,0 +1,169 @@
+use std::io::Write;
+
+use crate::{
+    base_message::Sendable,
+    errors::ServerError,
+    events::{Event, ModuleEventBody},
+    server::ServerOutput,
+    types::ModuleEventReason,
+};
+
+/// Collects `module` events and sends them in batches.
+///
+/// A debuggee can load hundreds of modules while it starts up. Instead of writing (and flushing)
+/// one message per module, the batcher holds the events back until `batch_size` of them are
+/// pending or [`ModuleBatcher::flush`] is called, and sends them with
+/// [`ServerOutput::send_all`]. Events for the same module are coalesced while pending, e.g. a
+/// module that is loaded and unloaded again before the batch goes out is not reported at all.
+#[derive(Debug, Clone)]
+pub struct ModuleBatcher {
+    pending: Vec<ModuleEventBody>,
+    batch_size: usize,
+}
+
+impl ModuleBatcher {
+    /// Create a batcher that sends once `batch_size` (at least 1) events are pending.
+    pub fn new(batch_size: usize) -> Self {
+        Self {
+            pending: Vec::new(),
+            batch_size: batch_size.max(1),
+        }
+    }
+
+    /// Queue a `module` event, sending the batch if it is full.
+    pub fn push<W: Write>(
+        &mut self,
+        event: ModuleEventBody,
+        output: &mut ServerOutput<W>,
+    ) -> Result<(), ServerError> {
+        self.coalesce(event);
+        if self.pending.len() >= self.batch_size {
+            self.flush(output)?;
+        }
+        Ok(())
+    }
+
+    /// Send all pending events, e.g. once the debuggee has finished starting up or stopped.
+    pub fn flush<W: Write>(&mut self, output: &mut ServerOutput<W>) -> Result<(), ServerError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        output.send_all(
+            self.pending
+                .drain(..)
+                .map(|body| Sendable::Event(Event::Module(body))),
+        )
+    }
+
+    /// The number of events waiting to be sent.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn coalesce(&mut self, event: ModuleEventBody) {
+        let Some(index) = self
+            .pending
+            .iter()
+            .position(|pending| pending.module.id == event.module.id)
+        else {
+            self.pending.push(event);
+            return;
+        };
+        let reason = match (self.pending[index].reason, event.reason) {
+            // The client never heard of the module, so it does not need to hear of it now
+            (ModuleEventReason::New, ModuleEventReason::Removed) => {
+                self.pending.remove(index);
+                return;
+            }
+            (ModuleEventReason::New, _) => ModuleEventReason::New,
+            (ModuleEventReason::Removed, ModuleEventReason::New) => ModuleEventReason::Changed,
+            (_, reason) => reason,
+        };
+        self.pending[index] = ModuleEventBody {
+            reason,
+            module: event.module,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::io::{BufReader, BufWriter, Cursor};
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{server::Server, types::Module, types::ModuleId};
+
+    /// Records what is written and counts how often the connection is flushed, i.e. how many
+    /// sends reach the client.
+    #[derive(Default, Clone)]
+    struct CountingWriter {
+        bytes: Rc<RefCell<Vec<u8>>>,
+        flushes: Rc<Cell<usize>>,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.bytes.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.flushes.set(self.flushes.get() + 1);
+            Ok(())
+        }
+    }
+
+    fn module_event(reason: ModuleEventReason, id: usize) -> ModuleEventBody {
+        ModuleEventBody {
+            reason,
+            module: Module {
+                id: ModuleId::String(format!("mod{id}")),
+                name: format!("mod{id}.so"),
+                path: None,
+                is_optimized: None,
+                is_user_code: None,
+                version: None,
+                symbol_status: None,
+                symbol_file_path: None,
+                date_time_stamp: None,
+                address_range: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_module_burst_is_batched() {
+        let writer = CountingWriter::default();
+        let mut server_in = Cursor::new(Vec::new());
+        let server = Server::new(
+            BufReader::new(&mut server_in),
+            BufWriter::new(writer.clone()),
+        );
+        let mut output = server.output.lock().unwrap();
+        let mut batcher = ModuleBatcher::new(32);
+
+        for id in 0..100 {
+            batcher
+                .push(module_event(ModuleEventReason::New, id), &mut output)
+                .unwrap();
+        }
+        // Loaded and immediately unloaded again: never reported
+        batcher
+            .push(module_event(ModuleEventReason::New, 100), &mut output)
+            .unwrap();
+        batcher
+            .push(module_event(ModuleEventReason::Removed, 100), &mut output)
+            .unwrap();
+        batcher.flush(&mut output).unwrap();
+        assert_eq!(batcher.pending(), 0);
+
+        output.send_event(Event::Initialized).unwrap();
+        let written = String::from_utf8(writer.bytes.borrow().clone()).unwrap();
+        assert_eq!(written.matches(r#""event":"module""#).count(), 100);
+        assert!(!written.contains("mod100.so"));
+        // 3 full batches, the final flush, and the initialized event
+        assert_eq!(writer.flushes.get(), 5);
+    }
+}
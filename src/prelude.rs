@@ -1,9 +1,12 @@
 #[doc(hidden)]
 pub use crate::{
     events::{self, Event},
+    handler::{DapHandler, dispatch},
     requests::{self, Command, Request},
     responses::{self, Response, ResponseBody},
     reverse_requests::{ReverseCommand, ReverseRequest},
-    server::Server,
     types,
 };
+
+#[cfg(feature = "std")]
+pub use crate::server::Server;
@@ -1,3 +1,10 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::path::{MAIN_SEPARATOR, PathBuf};
+
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -6,9 +13,9 @@ use crate::{
     prelude::{Response, ResponseBody},
     responses::ResponseMessage,
     types::{
-        DataBreakpoint, EvaluateArgumentsContext, ExceptionFilterOptions, ExceptionOptions,
-        FunctionBreakpoint, InstructionBreakpoint, Source, SourceBreakpoint, StackFrameFormat,
-        SteppingGranularity, ValueFormat, VariablesArgumentsFilter,
+        Capabilities, DataBreakpoint, EvaluateArgumentsContext, ExceptionFilterOptions,
+        ExceptionOptions, FunctionBreakpoint, InstructionBreakpoint, Source, SourceBreakpoint,
+        StackFrameFormat, SteppingGranularity, ValueFormat, VariablesArgumentsFilter,
     },
 };
 
@@ -21,7 +28,94 @@ pub enum PathFormat {
     Other(String),
 }
 
-//// Arguments for an Initialize request.
+impl PathFormat {
+    /// Parse a path sent by the client in this format into a normalized [`PathBuf`].
+    ///
+    /// Clients are not always consistent in how they spell paths, especially on Windows, so this
+    /// accepts `file:` URIs (for [`PathFormat::Uri`]), percent-encoded characters and any mix of
+    /// `/` and `\` separators. The result uses the platform's separator, drops empty and `.`
+    /// segments, and keeps UNC prefixes (`\\server\share`) and drive letters intact. Encoded
+    /// separators (`%2F`, `%5C`) stay encoded, as they belong to the name of a segment.
+    #[cfg(feature = "std")]
+    pub fn parse_path(&self, raw: &str) -> PathBuf {
+        let (path, is_file_uri) = match self {
+            PathFormat::Uri => strip_file_scheme(raw),
+            _ => (raw, false),
+        };
+
+        let is_separator = |c: char| c == '/' || c == '\\';
+        // Segments are decoded one by one, so an encoded separator can't split them
+        let segments: Vec<String> = path
+            .split(is_separator)
+            .filter(|segment| !segment.is_empty() && *segment != ".")
+            .map(percent_decode)
+            .collect();
+        let is_drive = |segment: &str| {
+            let bytes = segment.as_bytes();
+            bytes.len() == 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+        };
+
+        let mut normalized = String::with_capacity(path.len());
+        // The path of a URI like file:///C:/dir starts with a `/` before the drive letter
+        let uri_drive = is_file_uri && segments.first().is_some_and(|first| is_drive(first));
+        if path.starts_with(is_separator) && !uri_drive {
+            normalized.push(MAIN_SEPARATOR);
+            if path[1..].starts_with(is_separator) {
+                // UNC path, e.g. \\server\share
+                normalized.push(MAIN_SEPARATOR);
+            }
+        }
+        for (index, segment) in segments.iter().enumerate() {
+            if index > 0 {
+                normalized.push(MAIN_SEPARATOR);
+            }
+            normalized.push_str(segment);
+        }
+        PathBuf::from(normalized)
+    }
+}
+
+/// Strip the `file:` scheme of a URI, turning `file://server/share` into a UNC path and
+/// `file:///dir` into `/dir`. Returns whether `uri` was a `file:` URI.
+#[cfg(feature = "std")]
+fn strip_file_scheme(uri: &str) -> (&str, bool) {
+    let Some(rest) = uri.strip_prefix("file:") else {
+        return (uri, false);
+    };
+    match rest.strip_prefix("//") {
+        // An empty authority, e.g. file:///C:/dir or file:///tmp
+        Some(local) if local.starts_with('/') => (local, true),
+        // file://server/share keeps the leading `//` so it's parsed as a UNC path
+        _ => (rest, true),
+    }
+}
+
+#[cfg(feature = "std")]
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes
+            .get(index + 1..index + 3)
+            .and_then(|hex| core::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], hex) {
+            // Encoded separators are part of a segment name, not a separator
+            (b'%', Some(byte)) if byte != b'/' && byte != b'\\' => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Arguments for an Initialize request.
 /// In specification: [Initialize](https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Initialize)
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -63,7 +157,7 @@ pub struct InitializeArguments {
     pub supports_start_debugging_request: Option<bool>,
 }
 
-//// Arguments for an SetBreakpoints request.
+/// Arguments for an SetBreakpoints request.
 /// In specification: [SetBreakpoints](https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Initialize)
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -120,12 +214,13 @@ pub struct SetFunctionBreakpointsArguments {
     pub breakpoints: Vec<FunctionBreakpoint>,
 }
 
-//// Arguments for a Launch request.
+/// Arguments for a Launch request.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct LaunchRequestArguments {
     /// If true, the launch request should launch the program without enabling
     /// debugging.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub no_debug: Option<bool>,
     /// Arbitrary data from the previous, restarted session.
     /// The data is sent as the `restart` attribute of the `terminated` event.
@@ -133,21 +228,21 @@ pub struct LaunchRequestArguments {
     ///
     /// Rust-specific: this data must be a string. Server requiring storing binary data should use
     /// an encoding that is suitable for string (e.g. base85 or similar).
-    #[serde(rename = "__restart")]
+    #[serde(rename = "__restart", skip_serializing_if = "Option::is_none")]
     pub restart_data: Option<Value>,
     /// The request may include additional implementation specific attributes.
     #[serde(flatten)]
     pub additional_data: Option<Value>,
 }
 
-//// Arguments for an Attach request.
+/// Arguments for an Attach request.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AttachRequestArguments {
     /// Arbitrary data from the previous, restarted session.
     /// The data is sent as the `restart` attribute of the `terminated` event.
     /// The client should leave the data intact.
-    #[serde(rename = "__restart")]
+    #[serde(rename = "__restart", skip_serializing_if = "Option::is_none")]
     pub restart_data: Option<Value>,
 
     /// The request may include additional implementation specific attributes.
@@ -155,19 +250,23 @@ pub struct AttachRequestArguments {
     pub additional_data: Option<Value>,
 }
 
-//// Union of Attach and Launch arguments for the Restart request.
-//// Currently the same as LaunchRequestArguments but might not be in the future.
+/// Union of Attach and Launch arguments for the Restart request.
+/// Currently the same as LaunchRequestArguments but might not be in the future.
+///
+/// The implementation specific attributes of the original `launch` or `attach` request are kept
+/// in `additional_data`, so they round-trip unchanged when the arguments are sent back.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AttachOrLaunchArguments {
     /// If true, the launch request should launch the program without enabling
     /// debugging.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub no_debug: Option<bool>,
 
     /// Arbitrary data from the previous, restarted session.
     /// The data is sent as the `restart` attribute of the `terminated` event.
     /// The client should leave the data intact.
-    #[serde(rename = "__restart")]
+    #[serde(rename = "__restart", skip_serializing_if = "Option::is_none")]
     pub restart_data: Option<Value>,
 
     /// The request may include additional implementation specific attributes.
@@ -175,7 +274,7 @@ pub struct AttachOrLaunchArguments {
     pub additional_data: Option<Value>,
 }
 
-//// Arguments for a BreakpointLocations request.
+/// Arguments for a BreakpointLocations request.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct BreakpointLocationsArguments {
@@ -200,7 +299,7 @@ pub struct BreakpointLocationsArguments {
     pub end_column: Option<i64>,
 }
 
-//// Arguments for a Completions request.
+/// Arguments for a Completions request.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct CompletionsArguments {
@@ -219,7 +318,51 @@ pub struct CompletionsArguments {
     pub line: Option<i64>,
 }
 
-//// Arguments for a Continue request.
+impl CompletionsArguments {
+    /// The partial identifier (letters, digits, `_`, `.` and `:`) right before the cursor, i.e.
+    /// what completion proposals have to start with.
+    ///
+    /// `line` and `column` are interpreted according to the `linesStartAt1` and
+    /// `columnsStartAt1` settings the client sent in its `initialize` request.
+    pub fn prefix(&self, client: &InitializeArguments) -> &str {
+        let line_base = if client.lines_start_at1 == Some(false) {
+            0
+        } else {
+            1
+        };
+        let column_base = if client.columns_start_at1 == Some(false) {
+            0
+        } else {
+            1
+        };
+        let line_index = self.line.map_or(0, |line| (line - line_base).max(0)) as usize;
+        let Some(line) = self.text.split('\n').nth(line_index) else {
+            return "";
+        };
+
+        // `column` counts UTF-16 code units, find the matching byte offset
+        let mut units = (self.column - column_base).max(0) as usize;
+        let mut cursor = line.len();
+        for (offset, c) in line.char_indices() {
+            if units == 0 {
+                cursor = offset;
+                break;
+            }
+            units = units.saturating_sub(c.len_utf16());
+        }
+
+        let before = &line[..cursor];
+        let prefix_len: usize = before
+            .chars()
+            .rev()
+            .take_while(|&c| c.is_alphanumeric() || matches!(c, '_' | '.' | ':'))
+            .map(char::len_utf8)
+            .sum();
+        &before[before.len() - prefix_len..]
+    }
+}
+
+/// Arguments for a Continue request.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ContinueArguments {
@@ -232,7 +375,7 @@ pub struct ContinueArguments {
     pub single_thread: Option<bool>,
 }
 
-//// Arguments for a DataBreakpointInfo request.
+/// Arguments for a DataBreakpointInfo request.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct DataBreakpointInfoArguments {
@@ -249,9 +392,68 @@ pub struct DataBreakpointInfoArguments {
     /// If not specified, the expression is evaluated in the global scope. When
     /// `variablesReference` is specified, this property has no effect.
     pub frame_id: Option<i64>,
+    /// If specified, a debug adapter should return information for the range of memory
+    /// extending `bytes` number of bytes from the address or variable specified by `name`.
+    /// Breakpoints set using the resulting data ID should pause on data access anywhere within
+    /// that range.
+    /// Clients may set this property only if the `supportsDataBreakpointBytes` capability is
+    /// true.
+    pub bytes: Option<i64>,
+    /// If `true`, the `name` is a memory address and the debugger should interpret it as a
+    /// decimal value, or hex value if it is prefixed with `0x`.
+    /// Clients may set this property only if the `supportsDataBreakpointBytes` capability is
+    /// true.
+    pub as_address: Option<bool>,
 }
 
-//// Arguments for a Disassemble request.
+/// The form of a `dataBreakpointInfo` request, see [`DataBreakpointInfoArguments::query`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataBreakpointQuery<'a> {
+    /// A child variable `name` of the container identified by `variables_reference`.
+    Variable {
+        variables_reference: i64,
+        name: &'a str,
+    },
+    /// An expression evaluated in the scope of `frame_id`, or in the global scope if no frame
+    /// was given.
+    Expression {
+        expression: &'a str,
+        frame_id: Option<i64>,
+    },
+    /// A memory address (decimal, or hex if prefixed with `0x`).
+    Address {
+        address: &'a str,
+        bytes: Option<i64>,
+    },
+}
+
+impl DataBreakpointInfoArguments {
+    /// Determine which form of the request the client used.
+    ///
+    /// `asAddress` takes precedence over everything else; otherwise a `variablesReference`
+    /// makes `name` a child variable (and `frameId` has no effect), and without one `name` is an
+    /// expression.
+    pub fn query(&self) -> DataBreakpointQuery<'_> {
+        if self.as_address == Some(true) {
+            DataBreakpointQuery::Address {
+                address: &self.name,
+                bytes: self.bytes,
+            }
+        } else if let Some(variables_reference) = self.variables_reference {
+            DataBreakpointQuery::Variable {
+                variables_reference,
+                name: &self.name,
+            }
+        } else {
+            DataBreakpointQuery::Expression {
+                expression: &self.name,
+                frame_id: self.frame_id,
+            }
+        }
+    }
+}
+
+/// Arguments for a Disassemble request.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct DisassembleArguments {
@@ -275,7 +477,7 @@ pub struct DisassembleArguments {
     pub resolve_symbols: Option<bool>,
 }
 
-//// Arguments for a Disconnect request.
+/// Arguments for a Disconnect request.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct DisconnectArguments {
@@ -296,7 +498,7 @@ pub struct DisconnectArguments {
     pub suspend_debuggee: Option<bool>,
 }
 
-//// Arguments for a Evaluate request.
+/// Arguments for a Evaluate request.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct EvaluateArguments {
@@ -324,6 +526,52 @@ pub struct EvaluateArguments {
     pub format: Option<ValueFormat>,
 }
 
+/// Text typed into the debug console (REPL) together with the cursor position.
+///
+/// Editors typically send `completions` requests while the user is typing and an `evaluate`
+/// request once the input is submitted. Deriving both argument sets from the same `ReplInput`
+/// keeps the text, frame and column convention consistent between the two.
+#[derive(Debug, Default, Clone)]
+pub struct ReplInput {
+    /// The text entered by the user.
+    pub text: String,
+    /// The cursor position within `text`. It is measured in UTF-16 code units and follows the
+    /// client capability `columnsStartAt1`, just like `CompletionsArguments::column`.
+    pub column: i64,
+    /// The stack frame the input refers to. If not specified, the global scope is used.
+    pub frame_id: Option<i64>,
+}
+
+impl ReplInput {
+    pub fn new(text: impl Into<String>, column: i64, frame_id: Option<i64>) -> Self {
+        Self {
+            text: text.into(),
+            column,
+            frame_id,
+        }
+    }
+
+    /// Arguments for evaluating the whole input in the `repl` context.
+    pub fn evaluate_arguments(&self) -> EvaluateArguments {
+        EvaluateArguments {
+            expression: self.text.clone(),
+            frame_id: self.frame_id,
+            context: Some(EvaluateArgumentsContext::Repl),
+            format: None,
+        }
+    }
+
+    /// Arguments for requesting completions at the cursor position.
+    pub fn completions_arguments(&self) -> CompletionsArguments {
+        CompletionsArguments {
+            frame_id: self.frame_id,
+            text: self.text.clone(),
+            column: self.column,
+            line: None,
+        }
+    }
+}
+
 /// Arguments for a ExceptionInfo request.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -356,6 +604,14 @@ pub struct GotoTargetsArguments {
     pub column: Option<i64>,
 }
 
+/// Arguments for a Locations request.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationsArguments {
+    /// Location reference to resolve.
+    pub location_reference: i64,
+}
+
 /// Arguments for a Modules request.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -402,6 +658,37 @@ pub struct ReadMemoryArguments {
     pub count: i64,
 }
 
+impl ReadMemoryArguments {
+    /// The memory reference, the offset (0 if not given) and the number of bytes to read.
+    pub fn range(&self) -> (String, i64, usize) {
+        (
+            self.memory_reference.clone(),
+            self.offset.unwrap_or(0),
+            usize::try_from(self.count).unwrap_or(0),
+        )
+    }
+
+    /// Split the read into consecutive reads of at most `page_size` (at least 1) bytes each,
+    /// e.g. for a client that reads a large range piecewise.
+    ///
+    /// Paging stops before the first page whose offset does not fit into an `i64`.
+    pub fn pages(&self, page_size: usize) -> Vec<ReadMemoryArguments> {
+        let (memory_reference, offset, count) = self.range();
+        let page_size = page_size.max(1);
+        (0..count)
+            .step_by(page_size)
+            .map_while(|start| {
+                let page_offset = offset.checked_add(i64::try_from(start).ok()?)?;
+                Some(ReadMemoryArguments {
+                    memory_reference: memory_reference.clone(),
+                    offset: Some(page_offset),
+                    count: i64::try_from(page_size.min(count - start)).ok()?,
+                })
+            })
+            .collect()
+    }
+}
+
 /// Arguments for a ReadMemory request.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -744,6 +1031,10 @@ pub enum Command {
     ///
     /// Specification: [LoadedSources request](https://microsoft.github.io/debug-adapter-protocol/specification#Requests_LoadedSources)
     LoadedSources,
+    /// Looks up information about a location reference previously returned by the debug adapter.
+    ///
+    /// Specification: [Locations request](https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Locations)
+    Locations(LocationsArguments),
     /// Modules can be retrieved from the debug adapter with this request which can either return
     /// all modules or a range of modules to support paging.
     /// Clients should only call this request if the corresponding capability
@@ -947,6 +1238,137 @@ pub enum Command {
     ///
     /// Specification: [CancelRequest](https://microsoft.github.io/debug-adapter-protocol/specification#Base_Protocol_Cancel)
     Cancel(CancelArguments),
+    /// A request that is not part of the specification, e.g. one specific to a debug adapter.
+    #[serde(untagged)]
+    Custom(CustomCommand),
+}
+
+/// A request command this crate does not know about, with its raw arguments.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CustomCommand {
+    /// The name of the command.
+    pub command: String,
+    /// The arguments of the request, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<Value>,
+}
+
+impl Command {
+    /// The name of the command as it appears in the `command` attribute of the request.
+    pub fn name(&self) -> &str {
+        match self {
+            Command::Attach(_) => "attach",
+            Command::BreakpointLocations(_) => "breakpointLocations",
+            Command::Completions(_) => "completions",
+            Command::ConfigurationDone => "configurationDone",
+            Command::Continue(_) => "continue",
+            Command::DataBreakpointInfo(_) => "dataBreakpointInfo",
+            Command::Disassemble(_) => "disassemble",
+            Command::Disconnect(_) => "disconnect",
+            Command::Evaluate(_) => "evaluate",
+            Command::ExceptionInfo(_) => "exceptionInfo",
+            Command::Goto(_) => "goto",
+            Command::GotoTargets(_) => "gotoTargets",
+            Command::Initialize(_) => "initialize",
+            Command::Launch(_) => "launch",
+            Command::LoadedSources => "loadedSources",
+            Command::Locations(_) => "locations",
+            Command::Modules(_) => "modules",
+            Command::Next(_) => "next",
+            Command::Pause(_) => "pause",
+            Command::ReadMemory(_) => "readMemory",
+            Command::Restart(_) => "restart",
+            Command::RestartFrame(_) => "restartFrame",
+            Command::ReverseContinue(_) => "reverseContinue",
+            Command::Scopes(_) => "scopes",
+            Command::SetBreakpoints(_) => "setBreakpoints",
+            Command::SetDataBreakpoints(_) => "setDataBreakpoints",
+            Command::SetExceptionBreakpoints(_) => "setExceptionBreakpoints",
+            Command::SetExpression(_) => "setExpression",
+            Command::SetFunctionBreakpoints(_) => "setFunctionBreakpoints",
+            Command::SetInstructionBreakpoints(_) => "setInstructionBreakpoints",
+            Command::SetVariable(_) => "setVariable",
+            Command::Source(_) => "source",
+            Command::StackTrace(_) => "stackTrace",
+            Command::StepBack(_) => "stepBack",
+            Command::StepIn(_) => "stepIn",
+            Command::StepInTargets(_) => "stepInTargets",
+            Command::StepOut(_) => "stepOut",
+            Command::Terminate(_) => "terminate",
+            Command::TerminateThreads(_) => "terminateThreads",
+            Command::Threads => "threads",
+            Command::Variables(_) => "variables",
+            Command::WriteMemory(_) => "writeMemory",
+            Command::Cancel(_) => "cancel",
+            Command::Custom(custom) => &custom.command,
+        }
+    }
+
+    /// The adapter capability this request depends on, if it is missing from `capabilities`.
+    ///
+    /// Clients must only send some requests if the adapter advertised the corresponding
+    /// capability in its `initialize` response. This covers data breakpoints, `completions`,
+    /// `modules`, `readMemory`, `restartFrame`, `disassemble`, `loadedSources`,
+    /// `setInstructionBreakpoints`, `terminateThreads` and `setExpression`: for these it returns
+    /// the name of the capability when it is not set. For all other requests, including ones
+    /// that some clients send without checking their capability, it returns `None`.
+    pub fn missing_capability(&self, capabilities: &Capabilities) -> Option<&'static str> {
+        let (name, value) = match self {
+            Command::DataBreakpointInfo(_) | Command::SetDataBreakpoints(_)
+                if capabilities.supports_data_breakpoints != Some(true) =>
+            {
+                (
+                    "supportsDataBreakpoints",
+                    capabilities.supports_data_breakpoints,
+                )
+            }
+            Command::DataBreakpointInfo(args)
+                if args.bytes.is_some() || args.as_address == Some(true) =>
+            {
+                (
+                    "supportsDataBreakpointBytes",
+                    capabilities.supports_data_breakpoint_bytes,
+                )
+            }
+            Command::Completions(_) => (
+                "supportsCompletionsRequest",
+                capabilities.supports_completions_request,
+            ),
+            Command::Modules(_) => (
+                "supportsModulesRequest",
+                capabilities.supports_modules_request,
+            ),
+            Command::ReadMemory(_) => (
+                "supportsReadMemoryRequest",
+                capabilities.supports_read_memory_request,
+            ),
+            Command::RestartFrame(_) => {
+                ("supportsRestartFrame", capabilities.supports_restart_frame)
+            }
+            Command::Disassemble(_) => (
+                "supportsDisassembleRequest",
+                capabilities.supports_disassemble_request,
+            ),
+            Command::LoadedSources => (
+                "supportsLoadedSourcesRequest",
+                capabilities.supports_loaded_sources_request,
+            ),
+            Command::SetInstructionBreakpoints(_) => (
+                "supportsInstructionBreakpoints",
+                capabilities.supports_instruction_breakpoints,
+            ),
+            Command::TerminateThreads(_) => (
+                "supportsTerminateThreadsRequest",
+                capabilities.supports_terminate_threads_request,
+            ),
+            Command::SetExpression(_) => (
+                "supportsSetExpression",
+                capabilities.supports_set_expression,
+            ),
+            _ => return None,
+        };
+        (value != Some(true)).then_some(name)
+    }
 }
 
 // Custom deserializer to handle both missing arguments and empty arguments object
@@ -955,8 +1377,8 @@ impl<'de> Deserialize<'de> for Command {
     where
         D: serde::Deserializer<'de>,
     {
+        use core::fmt;
         use serde::de::{self, MapAccess, Visitor};
-        use std::fmt;
 
         struct CommandVisitor;
 
@@ -1106,6 +1528,13 @@ impl<'de> Deserialize<'de> for Command {
                         }
                         Ok(Command::LoadedSources)
                     }
+                    "locations" => {
+                        let args =
+                            arguments.ok_or_else(|| de::Error::missing_field("arguments"))?;
+                        Ok(Command::Locations(
+                            serde_json::from_value(args).map_err(de::Error::custom)?,
+                        ))
+                    }
                     "modules" => {
                         let args =
                             arguments.ok_or_else(|| de::Error::missing_field("arguments"))?;
@@ -1294,53 +1723,10 @@ impl<'de> Deserialize<'de> for Command {
                             serde_json::from_value(args).map_err(de::Error::custom)?,
                         ))
                     }
-                    _ => Err(de::Error::unknown_variant(
-                        &command_name,
-                        &[
-                            "attach",
-                            "breakpointLocations",
-                            "completions",
-                            "configurationDone",
-                            "continue",
-                            "dataBreakpointInfo",
-                            "disassemble",
-                            "disconnect",
-                            "evaluate",
-                            "exceptionInfo",
-                            "goto",
-                            "gotoTargets",
-                            "initialize",
-                            "launch",
-                            "loadedSources",
-                            "modules",
-                            "next",
-                            "pause",
-                            "readMemory",
-                            "restart",
-                            "restartFrame",
-                            "reverseContinue",
-                            "scopes",
-                            "setBreakpoints",
-                            "setDataBreakpoints",
-                            "setExceptionBreakpoints",
-                            "setExpression",
-                            "setFunctionBreakpoints",
-                            "setInstructionBreakpoints",
-                            "setVariable",
-                            "source",
-                            "stackTrace",
-                            "stepBack",
-                            "stepIn",
-                            "stepInTargets",
-                            "stepOut",
-                            "terminate",
-                            "terminateThreads",
-                            "threads",
-                            "variables",
-                            "writeMemory",
-                            "cancel",
-                        ],
-                    )),
+                    _ => Ok(Command::Custom(CustomCommand {
+                        command: command_name,
+                        arguments,
+                    })),
                 }
             }
         }
@@ -1433,6 +1819,13 @@ impl Request {
                 body: Some(ResponseBody::Attach),
                 error: None,
             }),
+            Command::Cancel(_) => Ok(Response {
+                request_seq: self.seq,
+                success: true,
+                message: None,
+                body: Some(ResponseBody::Cancel),
+                error: None,
+            }),
             Command::ConfigurationDone => Ok(Response {
                 request_seq: self.seq,
                 success: true,
@@ -1536,10 +1929,57 @@ impl Request {
     }
 }
 
+/// Drop `setBreakpoints` requests that are superseded by a later `setBreakpoints` request for
+/// the same source in `requests`.
+///
+/// Since each `setBreakpoints` request replaces all breakpoints of its source, only the most
+/// recent one for a source has to be processed. This is useful for slow debuggers that queue
+/// up requests while editors send them in rapid succession as the user edits breakpoints.
+///
+/// Returns the requests that still need handling (in their original order) and cancellation
+/// responses for the dropped ones, which should be sent to the client so it doesn't wait on them.
+/// Sources are identified by their `sourceReference` if it is > 0 and by their `path` otherwise;
+/// requests for sources that have neither are always kept.
+pub fn coalesce_set_breakpoints(requests: Vec<Request>) -> (Vec<Request>, Vec<Response>) {
+    fn source_key(request: &Request) -> Option<(Option<i32>, Option<&str>)> {
+        match &request.command {
+            Command::SetBreakpoints(args) => match args.source.source_reference {
+                Some(reference) if reference > 0 => Some((Some(reference), None)),
+                _ => args.source.path.as_deref().map(|path| (None, Some(path))),
+            },
+            _ => None,
+        }
+    }
+
+    let superseded: Vec<bool> = requests
+        .iter()
+        .enumerate()
+        .map(|(index, request)| match source_key(request) {
+            Some(key) => requests[index + 1..]
+                .iter()
+                .any(|later| source_key(later) == Some(key)),
+            None => false,
+        })
+        .collect();
+
+    let mut kept = Vec::new();
+    let mut dropped = Vec::new();
+    for (request, superseded) in requests.into_iter().zip(superseded) {
+        if superseded {
+            dropped.push(request.cancellation());
+        } else {
+            kept.push(request);
+        }
+    }
+    (kept, dropped)
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json::json;
 
+    use std::path::MAIN_SEPARATOR_STR;
+
     use super::*;
 
     #[test]
@@ -1638,4 +2078,341 @@ mod tests {
             _ => panic!("Expected Command::Continue"),
         }
     }
+
+    #[test]
+    fn test_custom_command() {
+        let req: Request = serde_json::from_value(json!({
+            "seq": 9,
+            "type": "request",
+            "command": "emmy/hotReload",
+            "arguments": { "file": "main.lua" }
+        }))
+        .unwrap();
+        assert_eq!(req.command.name(), "emmy/hotReload");
+        let value = serde_json::to_value(&req.command).unwrap();
+        assert_eq!(
+            value,
+            json!({ "command": "emmy/hotReload", "arguments": { "file": "main.lua" } })
+        );
+        assert_eq!(Command::LoadedSources.name(), "loadedSources");
+    }
+
+    #[test]
+    fn test_read_memory_pages() {
+        let args = ReadMemoryArguments {
+            memory_reference: "0x1000".to_string(),
+            offset: Some(-16),
+            count: 2500,
+        };
+        assert_eq!(args.range(), ("0x1000".to_string(), -16, 2500));
+
+        let pages = args.pages(1024);
+        let ranges: Vec<_> = pages.iter().map(|page| (page.offset, page.count)).collect();
+        assert_eq!(
+            ranges,
+            [(Some(-16), 1024), (Some(1008), 1024), (Some(2032), 452)]
+        );
+        assert!(pages.iter().all(|page| page.memory_reference == "0x1000"));
+
+        // Pages past i64::MAX are left out instead of overflowing
+        let near_end = ReadMemoryArguments {
+            offset: Some(i64::MAX - 1500),
+            ..args.clone()
+        };
+        let ranges: Vec<_> = near_end
+            .pages(1024)
+            .iter()
+            .map(|page| (page.offset, page.count))
+            .collect();
+        assert_eq!(
+            ranges,
+            [(Some(i64::MAX - 1500), 1024), (Some(i64::MAX - 476), 1024)]
+        );
+
+        let empty = ReadMemoryArguments { count: 0, ..args };
+        assert_eq!(empty.range().2, 0);
+        assert!(empty.pages(1024).is_empty());
+    }
+
+    #[test]
+    fn test_custom_command_arguments_roundtrip() {
+        let arguments = json!({ "depth": 2, "filter": ["table", null], "verbose": true });
+        let req: Request = serde_json::from_value(json!({
+            "seq": 4,
+            "type": "request",
+            "command": "mydbg/foo",
+            "arguments": arguments
+        }))
+        .unwrap();
+        match &req.command {
+            Command::Custom(custom) => {
+                assert_eq!(custom.command, "mydbg/foo");
+                assert_eq!(custom.arguments.as_ref(), Some(&arguments));
+            }
+            _ => panic!("Expected Command::Custom"),
+        }
+        let roundtrip: Request =
+            serde_json::from_value(serde_json::to_value(&req).unwrap()).unwrap();
+        assert!(matches!(
+            roundtrip.command,
+            Command::Custom(CustomCommand { command, arguments: Some(args) })
+                if command == "mydbg/foo" && args == arguments
+        ));
+
+        let req: Request =
+            serde_json::from_value(json!({ "seq": 5, "type": "request", "command": "mydbg/bar" }))
+                .unwrap();
+        assert!(matches!(
+            req.command,
+            Command::Custom(CustomCommand {
+                arguments: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_repl_input_arguments() {
+        let input = ReplInput::new("player.inv", 8, Some(3));
+
+        let evaluate = input.evaluate_arguments();
+        assert_eq!(evaluate.expression, "player.inv");
+        assert_eq!(evaluate.frame_id, Some(3));
+        assert!(matches!(
+            evaluate.context,
+            Some(EvaluateArgumentsContext::Repl)
+        ));
+
+        let completions = input.completions_arguments();
+        assert_eq!(completions.text, evaluate.expression);
+        assert_eq!(completions.frame_id, evaluate.frame_id);
+        assert_eq!(completions.column, 8);
+    }
+
+    #[test]
+    fn test_set_function_breakpoints() {
+        let req: Request = serde_json::from_value(json!({
+            "seq": 4,
+            "type": "request",
+            "command": "setFunctionBreakpoints",
+            "arguments": {
+                "breakpoints": [
+                    { "name": "Player:update" },
+                    { "name": "spawn", "condition": "level > 2", "hitCondition": "3" }
+                ]
+            }
+        }))
+        .unwrap();
+        let Command::SetFunctionBreakpoints(args) = &req.command else {
+            panic!("Expected Command::SetFunctionBreakpoints");
+        };
+        assert_eq!(args.breakpoints.len(), 2);
+        let spawn = &args.breakpoints[1];
+        assert_eq!(spawn.name, "spawn");
+        assert_eq!(spawn.condition.as_deref(), Some("level > 2"));
+        assert_eq!(spawn.hit_condition.as_deref(), Some("3"));
+
+        let built = FunctionBreakpoint::new("spawn")
+            .with_condition("level > 2")
+            .with_hit_condition("3");
+        assert_eq!(
+            serde_json::to_value(&built).unwrap(),
+            json!({ "name": "spawn", "condition": "level > 2", "hitCondition": "3" })
+        );
+
+        let breakpoints = (0..args.breakpoints.len() as i64)
+            .map(|id| crate::types::Breakpoint {
+                id: Some(id),
+                verified: true,
+                ..Default::default()
+            })
+            .collect();
+        let response = req.success(ResponseBody::SetFunctionBreakpoints(
+            crate::responses::SetFunctionBreakpointsResponse { breakpoints },
+        ));
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value["request_seq"], 4);
+        assert_eq!(value["command"], "setFunctionBreakpoints");
+        assert_eq!(value["body"]["breakpoints"][1]["id"], 1);
+        assert_eq!(value["body"]["breakpoints"][1]["verified"], true);
+    }
+
+    #[test]
+    fn test_completions_prefix() {
+        let client = InitializeArguments::default();
+        let args = CompletionsArguments {
+            frame_id: None,
+            text: "print(player.inv".to_string(),
+            column: 17,
+            line: None,
+        };
+        assert_eq!(args.prefix(&client), "player.inv");
+
+        // Cursor in the middle of the text, 0-based columns
+        let client = InitializeArguments {
+            columns_start_at1: Some(false),
+            ..Default::default()
+        };
+        let args = CompletionsArguments { column: 6, ..args };
+        assert_eq!(args.prefix(&client), "");
+        let args = CompletionsArguments {
+            text: "x = 1\nlocal ä = self:up".to_string(),
+            column: 17,
+            line: Some(2),
+            ..args
+        };
+        let client = InitializeArguments::default();
+        assert_eq!(args.prefix(&client), "self:u");
+    }
+
+    #[test]
+    fn test_data_breakpoint_info_query() {
+        let query_of = |arguments: Value| {
+            let request: Request = serde_json::from_value(json!({
+                "type": "request",
+                "seq": 1,
+                "command": "dataBreakpointInfo",
+                "arguments": arguments
+            }))
+            .unwrap();
+            match request.command {
+                Command::DataBreakpointInfo(args) => args,
+                _ => panic!("Expected Command::DataBreakpointInfo"),
+            }
+        };
+
+        let args = query_of(json!({"name": "count", "variablesReference": 5, "frameId": 2}));
+        assert_eq!(
+            args.query(),
+            DataBreakpointQuery::Variable {
+                variables_reference: 5,
+                name: "count"
+            }
+        );
+
+        let args = query_of(json!({"name": "player.hp", "frameId": 2}));
+        assert_eq!(
+            args.query(),
+            DataBreakpointQuery::Expression {
+                expression: "player.hp",
+                frame_id: Some(2)
+            }
+        );
+
+        let args = query_of(json!({"name": "0x7ffe1000", "asAddress": true, "bytes": 8}));
+        assert_eq!(
+            args.query(),
+            DataBreakpointQuery::Address {
+                address: "0x7ffe1000",
+                bytes: Some(8)
+            }
+        );
+    }
+
+    #[test]
+    fn test_coalesce_set_breakpoints() {
+        let set_breakpoints = |seq: i64, path: &str, line: i64| -> Request {
+            serde_json::from_value(json!({
+                "type": "request",
+                "seq": seq,
+                "command": "setBreakpoints",
+                "arguments": {"source": {"path": path}, "breakpoints": [{"line": line}]}
+            }))
+            .unwrap()
+        };
+        let requests = vec![
+            set_breakpoints(1, "main.lua", 10),
+            set_breakpoints(2, "util.lua", 4),
+            set_breakpoints(3, "main.lua", 11),
+        ];
+
+        let (kept, dropped) = coalesce_set_breakpoints(requests);
+        assert_eq!(kept.iter().map(|r| r.seq).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].request_seq, 1);
+        assert!(!dropped[0].success);
+        assert!(matches!(
+            dropped[0].message,
+            Some(ResponseMessage::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn test_parse_unc_path() {
+        let expected = PathBuf::from(
+            ["", "", "server", "share", "my project", "main.lua"].join(MAIN_SEPARATOR_STR),
+        );
+        assert_eq!(
+            PathFormat::Path.parse_path(r"\\server\share/my%20project\./main.lua"),
+            expected
+        );
+        assert_eq!(
+            PathFormat::Uri.parse_path("file://server/share/my%20project/main.lua"),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_parse_drive_path() {
+        let expected = PathBuf::from(["C:", "Users", "dev", "main.lua"].join(MAIN_SEPARATOR_STR));
+        assert_eq!(
+            PathFormat::Path.parse_path(r"C:\Users/dev\\main.lua"),
+            expected
+        );
+        assert_eq!(
+            PathFormat::Uri.parse_path("file:///C:/Users/dev/main.lua"),
+            expected
+        );
+        // VS Code encodes the colon of the drive letter
+        let expected = PathBuf::from(["c:", "Users", "x.lua"].join(MAIN_SEPARATOR_STR));
+        assert_eq!(
+            PathFormat::Uri.parse_path("file:///c%3A/Users/x.lua"),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_parse_path_keeps_encoded_separators() {
+        let expected = PathBuf::from(["", "tmp", "a%2Fb.lua"].join(MAIN_SEPARATOR_STR));
+        assert_eq!(
+            PathFormat::Uri.parse_path("file:///tmp/a%2Fb.lua"),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_launch_arguments_keep_unknown_fields() {
+        let launch = serde_json::json!({
+            "seq": 2,
+            "type": "request",
+            "command": "launch",
+            "arguments": {
+                "noDebug": false,
+                "__sessionId": "4c1f",
+                "program": "main.lua",
+                "emmy": { "port": 9966 }
+            }
+        });
+        let request: Request = serde_json::from_value(launch.clone()).unwrap();
+        let Command::Launch(args) = &request.command else {
+            panic!("expected a launch request");
+        };
+        let extra = args.additional_data.as_ref().unwrap();
+        assert_eq!(extra["__sessionId"], "4c1f");
+        assert_eq!(extra["emmy"]["port"], 9966);
+
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value["arguments"], launch["arguments"]);
+
+        // The launch configuration survives being reused for a restart
+        let restart: AttachOrLaunchArguments =
+            serde_json::from_value(launch["arguments"].clone()).unwrap();
+        let value = serde_json::to_value(RestartArguments {
+            arguments: Some(restart),
+        })
+        .unwrap();
+        assert_eq!(value["arguments"]["__sessionId"], "4c1f");
+        assert_eq!(value["arguments"]["program"], "main.lua");
+        assert_eq!(value["arguments"]["emmy"]["port"], 9966);
+    }
 }
@@ -1,9 +1,17 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+use crate::requests::{InitializeArguments, Request, VariablesArguments};
 use crate::types::{
     Breakpoint, BreakpointLocation, Capabilities, CompletionItem, DataBreakpointAccessType,
-    DisassembledInstruction, ExceptionBreakMode, ExceptionDetails, GotoTarget, Message, Module,
-    Scope, Source, StackFrame, Thread, Variable, VariablePresentationHint,
+    DisassembledInstruction, ExceptionBreakMode, ExceptionDetails, GotoTarget,
+    InstructionBreakpoint, Message, Module, Scope, Source, StackFrame, Thread, Variable,
+    VariablePresentationHint,
 };
 
 /// Represents a response message that is either a cancellation or a short error string.
@@ -27,6 +35,25 @@ pub struct BreakpointLocationsResponse {
     pub breakpoints: Vec<BreakpointLocation>,
 }
 
+impl BreakpointLocationsResponse {
+    /// Build the response from the possible locations in any order. The protocol expects a
+    /// sorted set, so the locations are sorted by position and duplicates are dropped.
+    pub fn new(locations: impl IntoIterator<Item = BreakpointLocation>) -> Self {
+        let key = |location: &BreakpointLocation| {
+            (
+                location.line,
+                location.column,
+                location.end_line,
+                location.end_column,
+            )
+        };
+        let mut breakpoints: Vec<BreakpointLocation> = locations.into_iter().collect();
+        breakpoints.sort_by_key(key);
+        breakpoints.dedup_by(|a, b| key(a) == key(b));
+        Self { breakpoints }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct CompletionsResponse {
@@ -76,6 +103,15 @@ pub struct DisassembleResponse {
     pub instructions: Vec<DisassembledInstruction>,
 }
 
+impl DisassembleResponse {
+    /// A response listing `instructions` in the order of their addresses.
+    pub fn new(instructions: impl IntoIterator<Item = DisassembledInstruction>) -> Self {
+        Self {
+            instructions: instructions.into_iter().collect(),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct EvaluateResponse {
@@ -116,6 +152,42 @@ pub struct EvaluateResponse {
     /// capability `supportsMemoryReferences` is true.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub memory_reference: Option<String>,
+    /// A reference that allows the client to request the location where the
+    /// returned value is declared. For example, if a function pointer is
+    /// returned, the adapter may be able to look up the function's location.
+    /// This should be present only if the adapter is likely to be able to
+    /// resolve the location.
+    /// This reference shares the same lifetime as the `variablesReference`. See
+    /// [Lifetime of Object References](https://microsoft.github.io/debug-adapter-protocol/overview#lifetime-of-objects-references)
+    /// in the Overview section of the specification for details.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_location_reference: Option<i64>,
+}
+
+impl EvaluateResponse {
+    /// Create an evaluate result. A `variables_reference` > 0 makes the result structured.
+    pub fn new(result: impl Into<String>, variables_reference: i64) -> Self {
+        EvaluateResponse {
+            result: result.into(),
+            variables_reference,
+            ..Default::default()
+        }
+    }
+
+    /// Set the type of the result.
+    pub fn with_type(mut self, type_name: impl Into<String>) -> Self {
+        self.type_field = Some(type_name.into());
+        self
+    }
+
+    /// Adjust the result to what the client announced in its `initialize` request: the `type`
+    /// is dropped unless the client set `supportsVariableType`.
+    pub fn for_client(mut self, client: &InitializeArguments) -> Self {
+        if client.supports_variable_type != Some(true) {
+            self.type_field = None;
+        }
+        self
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
@@ -147,6 +219,32 @@ pub struct LoadedSourcesResponse {
     pub sources: Vec<Source>,
 }
 
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationsResponse {
+    /// The source containing the location; either `source.path` or
+    /// `source.sourceReference` must be specified.
+    pub source: Source,
+    /// The line number of the location. The client capability `linesStartAt1`
+    /// determines whether it is 0- or 1-based.
+    pub line: i64,
+    /// Position of the location within the `line`. It is measured in UTF-16 code
+    /// units and the client capability `columnsStartAt1` determines whether it
+    /// is 0- or 1-based. If no column is given, the first position in the start
+    /// line is assumed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<i64>,
+    /// End line of the location, present if the location refers to a range. The
+    /// client capability `linesStartAt1` determines whether it is 0- or 1-based.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<i64>,
+    /// End position of the location within `endLine`, present if the location
+    /// refers to a range. It is measured in UTF-16 code units and the client
+    /// capability `columnsStartAt1` determines whether it is 0- or 1-based.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_column: Option<i64>,
+}
+
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ModulesResponse {
@@ -230,6 +328,38 @@ pub struct SetInstructionBreakpointsResponse {
     pub breakpoints: Vec<Breakpoint>,
 }
 
+impl SetInstructionBreakpointsResponse {
+    /// Build the response for the requested `breakpoints`, in the same order.
+    ///
+    /// `verify` is called for every requested breakpoint: `Ok(id)` reports it as verified with
+    /// the given (optional) breakpoint id, `Err(message)` reports it as unverified with the
+    /// reason shown to the user. Each reported breakpoint carries the instruction reference and
+    /// offset it was requested for.
+    pub fn for_breakpoints<F>(breakpoints: &[InstructionBreakpoint], mut verify: F) -> Self
+    where
+        F: FnMut(&InstructionBreakpoint) -> Result<Option<i64>, String>,
+    {
+        let breakpoints = breakpoints
+            .iter()
+            .map(|requested| {
+                let (verified, id, message) = match verify(requested) {
+                    Ok(id) => (true, id, None),
+                    Err(message) => (false, None, Some(message)),
+                };
+                Breakpoint {
+                    id,
+                    verified,
+                    message,
+                    instruction_reference: Some(requested.instruction_reference.clone()),
+                    offset: requested.offset,
+                    ..Default::default()
+                }
+            })
+            .collect();
+        Self { breakpoints }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SetVariableResponse {
@@ -307,6 +437,37 @@ pub struct SetExpressionResponse {
     pub indexed_variables: Option<i32>,
 }
 
+impl SetExpressionResponse {
+    /// The new `value` of the assigned expression.
+    pub fn new(value: impl Into<String>) -> Self {
+        SetExpressionResponse {
+            value: value.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the type of the new value.
+    pub fn with_type(mut self, type_name: impl Into<String>) -> Self {
+        self.type_field = Some(type_name.into());
+        self
+    }
+
+    /// Make the new value structured, with its children retrieved through `reference`.
+    pub fn with_variables_reference(mut self, reference: i64) -> Self {
+        self.variables_reference = Some(reference);
+        self
+    }
+
+    /// Adjust the response to what the client announced in its `initialize` request: the `type`
+    /// is dropped unless the client set `supportsVariableType`.
+    pub fn for_client(mut self, client: &InitializeArguments) -> Self {
+        if client.supports_variable_type != Some(true) {
+            self.type_field = None;
+        }
+        self
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StackTraceResponse {
@@ -338,6 +499,65 @@ pub struct VariablesResponse {
     pub variables: Vec<Variable>,
 }
 
+impl VariablesResponse {
+    /// Build the page of indexed children requested by `args` for a variable that declared
+    /// `indexed_variables` children (its logical length).
+    ///
+    /// `start`/`count` are clamped to `0..indexed_variables`; a missing or zero `count` means
+    /// "up to the end". `element` is asked for every index in the page and returns `None` for
+    /// gaps in a sparse array. Gaps are left out unless `gap_value` is given, in which case a
+    /// [`Variable::hole`] with that value is returned in their place.
+    pub fn indexed_page<F>(
+        args: &VariablesArguments,
+        indexed_variables: i64,
+        element: F,
+        gap_value: Option<&str>,
+    ) -> Self
+    where
+        F: FnMut(i64) -> Option<Variable>,
+    {
+        Self::page_with(args, indexed_variables, element, |index| {
+            gap_value.map(|value| Variable {
+                value: value.to_string(),
+                ..Variable::hole(index)
+            })
+        })
+    }
+
+    /// Like [`VariablesResponse::indexed_page`], but every gap is filled with a
+    /// [`Variable::hole`], so the page always holds one variable per requested index.
+    pub fn contiguous_page<F>(args: &VariablesArguments, indexed_variables: i64, element: F) -> Self
+    where
+        F: FnMut(i64) -> Option<Variable>,
+    {
+        Self::page_with(args, indexed_variables, element, |index| {
+            Some(Variable::hole(index))
+        })
+    }
+
+    fn page_with<F, G>(
+        args: &VariablesArguments,
+        indexed_variables: i64,
+        mut element: F,
+        mut gap: G,
+    ) -> Self
+    where
+        F: FnMut(i64) -> Option<Variable>,
+        G: FnMut(i64) -> Option<Variable>,
+    {
+        let len = indexed_variables.max(0);
+        let start = args.start.unwrap_or(0).clamp(0, len);
+        let end = match args.count {
+            Some(count) if count > 0 => start.saturating_add(count).min(len),
+            _ => len,
+        };
+        let variables = (start..end)
+            .filter_map(|index| element(index).or_else(|| gap(index)))
+            .collect();
+        Self { variables }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct WriteMemoryResponse {
@@ -352,6 +572,19 @@ pub struct WriteMemoryResponse {
     pub bytes_written: Option<i64>,
 }
 
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RunInTerminalResponse {
+    /// The process ID. The value should be less than or equal to 2147483647
+    /// (2^31-1).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process_id: Option<i64>,
+    /// The process ID of the terminal shell. The value should be less than or
+    /// equal to 2147483647 (2^31-1).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell_process_id: Option<i64>,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(tag = "command", content = "body", rename_all = "camelCase")]
 pub enum ResponseBody {
@@ -363,6 +596,10 @@ pub enum ResponseBody {
     ///
     /// Specification: [BreakpointLocations request](https://microsoft.github.io/debug-adapter-protocol/specification#Requests_BreakpointLocations)
     BreakpointLocations(BreakpointLocationsResponse),
+    /// Response to `cancel` request. This is just an acknowledgement, so no body field is required.
+    ///
+    /// Specification: [Cancel request](https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Cancel)
+    Cancel,
     /// Response to a `completions` request
     ///
     /// Specification: [Completions request](https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Completions)
@@ -426,6 +663,10 @@ pub enum ResponseBody {
     ///
     /// Specification: [LoadedSources request](https://microsoft.github.io/debug-adapter-protocol/specification#Requests_LoadedSources)
     LoadedSources(LoadedSourcesResponse),
+    /// Response to `locations` request.
+    ///
+    /// Specification: [Locations request](https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Locations)
+    Locations(LocationsResponse),
     /// Response to `modules` request.
     ///
     /// Specification: [Modules request](https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Modules)
@@ -461,6 +702,10 @@ pub enum ResponseBody {
     ///
     /// Specification: [ReverseContinue request](https://microsoft.github.io/debug-adapter-protocol/specification#Requests_ReverseContinue)
     ReverseContinue,
+    /// Client response to the `runInTerminal` reverse request.
+    ///
+    /// Specification: [RunInTerminal](https://microsoft.github.io/debug-adapter-protocol/specification#Reverse_Requests_RunInTerminal)
+    RunInTerminal(RunInTerminalResponse),
     /// Response to scopes request.
     ///
     /// Specification: [Scopes request](https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Scopes)
@@ -525,6 +770,11 @@ pub enum ResponseBody {
     ///
     /// Specification: [Sources request](https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Source)
     Source(SourceResponse),
+    /// Client response to the `startDebugging` reverse request. This is just an acknowledgement, so
+    /// no body field is required.
+    ///
+    /// Specification: [StartDebugging](https://microsoft.github.io/debug-adapter-protocol/specification#Reverse_Requests_StartDebugging)
+    StartDebugging,
     /// Response to stackTrace request.
     ///
     /// Specification: [StackTrace request](https://microsoft.github.io/debug-adapter-protocol/specification#Requests_StackTrace)
@@ -567,6 +817,31 @@ pub enum ResponseBody {
     ///
     /// Specification: [WriteMemory request](https://microsoft.github.io/debug-adapter-protocol/specification#Requests_WriteMemory)
     WriteMemory(WriteMemoryResponse),
+    /// A response without a dedicated variant: the response to a custom request, or an error
+    /// response, which does not carry the regular body of its command.
+    #[serde(untagged)]
+    Custom(CustomResponse),
+}
+
+/// A response body for a command this crate does not model, see [`ResponseBody::Custom`].
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CustomResponse {
+    /// The name of the command of the request this responds to.
+    pub command: String,
+    /// The raw body of the response, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+/// The body of an error response.
+///
+/// Specification: [ErrorResponse](https://microsoft.github.io/debug-adapter-protocol/specification#Base_Protocol_ErrorResponse)
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorResponseBody {
+    /// A structured error message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Message>,
 }
 
 /// Represents response to the client.
@@ -577,8 +852,12 @@ pub enum ResponseBody {
 /// There is also no separate `ErrorResponse` struct. Instead, `Error` is just a variant of the
 /// ResponseBody enum.
 ///
+/// A failed response always decodes its body as [`ResponseBody::Custom`]: it carries error
+/// details (see [`ErrorResponseBody`]) rather than the regular body of its command, which could
+/// otherwise be mistaken for a typed body with only optional fields.
+///
 /// Specification: [Response](https://microsoft.github.io/debug-adapter-protocol/specification#Base_Protocol_Response)
-#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[derive(Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Response {
     /// Sequence number of the corresponding request.
@@ -605,13 +884,271 @@ pub struct Response {
     #[serde(flatten, skip_serializing_if = "Option::is_none")]
     pub body: Option<ResponseBody>,
     /// A structured error message.
+    ///
+    /// The specification sends it as `body.error` instead, which is what
+    /// [`Response::error_with`] does.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<Message>,
 }
 
+/// The wire shape of a [`Response`], before its body is decoded.
+#[derive(Deserialize)]
+struct RawResponse {
+    request_seq: i64,
+    success: bool,
+    message: Option<ResponseMessage>,
+    command: Option<String>,
+    body: Option<Value>,
+    error: Option<Message>,
+}
+
+impl<'de> Deserialize<'de> for Response {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = RawResponse::deserialize(deserializer)?;
+        let body = match raw.command {
+            None => None,
+            Some(command) if !raw.success => Some(ResponseBody::Custom(CustomResponse {
+                command,
+                body: raw.body.filter(|body| !body.is_null()),
+            })),
+            Some(command) => {
+                let mut tagged = serde_json::Map::new();
+                tagged.insert("command".to_string(), Value::String(command));
+                if let Some(body) = raw.body {
+                    tagged.insert("body".to_string(), body);
+                }
+                Some(
+                    ResponseBody::deserialize(Value::Object(tagged))
+                        .map_err(serde::de::Error::custom)?,
+                )
+            }
+        };
+        Ok(Response {
+            request_seq: raw.request_seq,
+            success: raw.success,
+            message: raw.message,
+            body,
+            error: raw.error,
+        })
+    }
+}
+
+impl Response {
+    /// Create a successful response to `request` carrying `body`.
+    ///
+    /// Unlike [`Request::success`] this borrows the request, so it can still be used afterwards.
+    /// The `command` of the response is the one of `body`, which must match the request's.
+    pub fn success_for(request: &Request, body: ResponseBody) -> Self {
+        debug_assert_eq!(
+            serde_json::to_value(&body)
+                .ok()
+                .and_then(|value| value.get("command")?.as_str().map(str::to_string))
+                .as_deref(),
+            Some(request.command.name()),
+            "response body does not match the request's command"
+        );
+        Response {
+            request_seq: request.seq,
+            success: true,
+            message: None,
+            body: Some(body),
+            error: None,
+        }
+    }
+
+    /// Create an error response to `request` carrying the structured `error` as its
+    /// `body.error`. The short error message is the formatted `error`.
+    pub fn error_with(request: &Request, error: Message) -> Self {
+        let message = error.formatted();
+        Self::structured_error(request.seq, request.command.name(), message, error)
+    }
+
+    pub(crate) fn structured_error(
+        request_seq: i64,
+        command: &str,
+        message: String,
+        error: Message,
+    ) -> Self {
+        let body = serde_json::to_value(ErrorResponseBody { error: Some(error) }).ok();
+        Response {
+            request_seq,
+            success: false,
+            message: Some(ResponseMessage::Error(message)),
+            body: Some(ResponseBody::Custom(CustomResponse {
+                command: command.to_string(),
+                body,
+            })),
+            error: None,
+        }
+    }
+
+    /// Create an error response to `request`, with `message` as its short error.
+    ///
+    /// The response names the request's command, as clients match responses by it, but carries
+    /// no body.
+    pub fn error_for(request: &Request, message: impl Into<String>) -> Self {
+        Self::command_error(request.seq, request.command.name(), message.into())
+    }
+
+    /// [`Response::error_for`] for a request that has already been consumed.
+    pub(crate) fn command_error(request_seq: i64, command: &str, message: String) -> Self {
+        Response {
+            request_seq,
+            success: false,
+            message: Some(ResponseMessage::Error(message)),
+            body: Some(ResponseBody::Custom(CustomResponse {
+                command: command.to_string(),
+                body: None,
+            })),
+            error: None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_set_instruction_breakpoints_response() {
+        let requested = [
+            InstructionBreakpoint {
+                instruction_reference: "0x1000".to_string(),
+                offset: Some(8),
+                ..Default::default()
+            },
+            InstructionBreakpoint {
+                instruction_reference: "0xdead".to_string(),
+                ..Default::default()
+            },
+        ];
+        let response = SetInstructionBreakpointsResponse::for_breakpoints(&requested, |bp| {
+            if bp.instruction_reference == "0x1000" {
+                Ok(Some(1))
+            } else {
+                Err("address is not mapped".to_string())
+            }
+        });
+
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(
+            value["breakpoints"],
+            serde_json::json!([
+                { "id": 1, "verified": true, "instructionReference": "0x1000", "offset": 8 },
+                {
+                    "verified": false,
+                    "message": "address is not mapped",
+                    "instructionReference": "0xdead"
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_variables_indexed_page_sparse() {
+        // A sparse array with a logical length of 1000 and only a few elements set
+        let element = |index: i64| {
+            [3, 4, 998]
+                .contains(&index)
+                .then(|| Variable::new(format!("[{index}]"), format!("item {index}")))
+        };
+        let args = |start, count| VariablesArguments {
+            variables_reference: 7,
+            start,
+            count,
+            ..Default::default()
+        };
+        let names = |response: VariablesResponse| {
+            response
+                .variables
+                .into_iter()
+                .map(|variable| (variable.name, variable.value))
+                .collect::<Vec<_>>()
+        };
+
+        let page = VariablesResponse::indexed_page(&args(Some(2), Some(3)), 1000, element, None);
+        assert_eq!(
+            names(page),
+            [
+                ("[3]".into(), "item 3".into()),
+                ("[4]".into(), "item 4".into())
+            ]
+        );
+
+        let page = VariablesResponse::indexed_page(
+            &args(Some(2), Some(3)),
+            1000,
+            element,
+            Some("<empty>"),
+        );
+        assert_eq!(
+            names(page),
+            [
+                ("[2]".into(), "<empty>".into()),
+                ("[3]".into(), "item 3".into()),
+                ("[4]".into(), "item 4".into())
+            ]
+        );
+
+        // Pages reaching past the declared length are clamped
+        let page = VariablesResponse::indexed_page(&args(Some(997), Some(10)), 1000, element, None);
+        assert_eq!(names(page), [("[998]".into(), "item 998".into())]);
+        let page = VariablesResponse::indexed_page(&args(Some(5000), None), 1000, element, None);
+        assert!(page.variables.is_empty());
+    }
+
+    #[test]
+    fn test_variables_contiguous_page() {
+        let items = [(0, "a"), (1, "b"), (4, "e")];
+        let element = |index: i64| {
+            items
+                .iter()
+                .find(|(i, _)| *i == index)
+                .map(|(_, value)| Variable::new(format!("[{index}]"), *value))
+        };
+        let args = VariablesArguments {
+            variables_reference: 7,
+            start: Some(1),
+            count: Some(4),
+            ..Default::default()
+        };
+
+        let page = VariablesResponse::contiguous_page(&args, 5, element);
+        let names = page
+            .variables
+            .iter()
+            .map(|variable| (variable.name.as_str(), variable.value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [("[1]", "b"), ("[2]", ""), ("[3]", ""), ("[4]", "e")]
+        );
+    }
+
+    #[test]
+    fn test_breakpoint_locations_response() {
+        let response = BreakpointLocationsResponse::new([
+            BreakpointLocation::new(12).with_column(5),
+            BreakpointLocation::new(3),
+            BreakpointLocation::new(12)
+                .with_column(1)
+                .with_end(14, Some(2)),
+            BreakpointLocation::new(3),
+        ]);
+        let value = serde_json::to_value(ResponseBody::BreakpointLocations(response)).unwrap();
+        assert_eq!(value["command"], "breakpointLocations");
+        assert_eq!(
+            value["body"],
+            serde_json::json!({
+                "breakpoints": [
+                    { "line": 3 },
+                    { "line": 12, "column": 1, "endLine": 14, "endColumn": 2 },
+                    { "line": 12, "column": 5 }
+                ]
+            })
+        );
+    }
+
     #[test]
     fn test_responsemessage_is_flattened() {
         let a = Response {
@@ -649,4 +1186,212 @@ mod test {
         assert!(val.get("message").unwrap().is_string());
         assert!(val.get("message").unwrap().as_str().unwrap() == "notStopped");
     }
+
+    #[test]
+    fn test_evaluate_value_location_reference() {
+        let mut registry = crate::locations::LocationRegistry::new();
+        let reference = registry.register(LocationsResponse {
+            line: 3,
+            ..Default::default()
+        });
+
+        let body = ResponseBody::Evaluate(EvaluateResponse {
+            result: "function: 0x55d1".to_string(),
+            value_location_reference: Some(reference),
+            ..Default::default()
+        });
+        let val = serde_json::to_value(body).unwrap();
+        assert_eq!(val["command"], "evaluate");
+        assert_eq!(val["body"]["valueLocationReference"], reference);
+        assert!(val["body"].get("memoryReference").is_none());
+        assert_eq!(registry.resolve(reference).unwrap().line, 3);
+    }
+
+    #[test]
+    fn test_evaluate_type_follows_client_support() {
+        let mut client = InitializeArguments {
+            supports_variable_type: Some(false),
+            ..Default::default()
+        };
+        let result = EvaluateResponse::new("42", 0)
+            .with_type("integer")
+            .for_client(&client);
+        let val = serde_json::to_value(&result).unwrap();
+        assert!(val.get("type").is_none());
+
+        client.supports_variable_type = Some(true);
+        let result = EvaluateResponse::new("42", 0)
+            .with_type("integer")
+            .for_client(&client);
+        let val = serde_json::to_value(&result).unwrap();
+        assert_eq!(val["type"], "integer");
+    }
+
+    #[test]
+    fn test_response_for_request() {
+        let request: Request =
+            serde_json::from_str(r#"{"seq": 7, "type": "request", "command": "threads"}"#).unwrap();
+
+        let response = Response::success_for(
+            &request,
+            ResponseBody::Threads(ThreadsResponse { threads: vec![] }),
+        );
+        assert_eq!(response.request_seq, 7);
+        assert!(response.success);
+
+        let response = Response::error_for(&request, "no process");
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["request_seq"], 7);
+        assert_eq!(json["command"], "threads");
+        assert_eq!(json["success"], false);
+        assert_eq!(json["message"], "no process");
+        assert!(json.get("body").is_none());
+
+        // An error response reads back with the command it names
+        let parsed: Response = serde_json::from_value(json).unwrap();
+        assert!(matches!(
+            parsed.body,
+            Some(ResponseBody::Custom(CustomResponse { command, body: None })) if command == "threads"
+        ));
+    }
+
+    #[test]
+    fn test_disassemble_response() {
+        let source = Source {
+            path: Some("/game/main.lua".to_string()),
+            ..Default::default()
+        };
+        let response = DisassembleResponse::new([
+            DisassembledInstruction::new(0x1000, "push rbp")
+                .with_bytes(&[0x55])
+                .with_symbol("main")
+                .with_location(source, 12, Some(3)),
+            DisassembledInstruction::new(0x1001, "mov rbp, rsp").with_bytes(&[0x48, 0x89, 0xe5]),
+        ]);
+
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(
+            json["instructions"][0],
+            serde_json::json!({
+                "address": "0x1000",
+                "instructionBytes": "55",
+                "instruction": "push rbp",
+                "symbol": "main",
+                "location": { "path": "/game/main.lua" },
+                "line": 12,
+                "column": 3
+            })
+        );
+        assert_eq!(
+            json["instructions"][1],
+            serde_json::json!({
+                "address": "0x1001",
+                "instructionBytes": "48 89 e5",
+                "instruction": "mov rbp, rsp"
+            })
+        );
+    }
+
+    #[test]
+    fn test_set_expression_response() {
+        let request: Request = serde_json::from_value(serde_json::json!({
+            "seq": 9,
+            "type": "request",
+            "command": "setExpression",
+            "arguments": {
+                "expression": "player.hp",
+                "value": "100",
+                "frameId": 2,
+                "format": { "hex": true }
+            }
+        }))
+        .unwrap();
+        let crate::requests::Command::SetExpression(args) = &request.command else {
+            panic!("expected a setExpression request");
+        };
+        assert_eq!(args.expression, "player.hp");
+        assert_eq!(args.value, "100");
+        assert_eq!(args.frame_id, Some(2));
+        assert_eq!(
+            args.format.as_ref().and_then(|format| format.hex),
+            Some(true)
+        );
+
+        let client = InitializeArguments {
+            supports_variable_type: Some(true),
+            ..Default::default()
+        };
+        let body = SetExpressionResponse::new("0x64")
+            .with_type("integer")
+            .for_client(&client);
+        let response = Response::success_for(&request, ResponseBody::SetExpression(body));
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value["request_seq"], 9);
+        assert_eq!(value["command"], "setExpression");
+        assert_eq!(
+            value["body"],
+            serde_json::json!({ "value": "0x64", "type": "integer" })
+        );
+
+        let body = SetExpressionResponse::new("{...}")
+            .with_type("table")
+            .with_variables_reference(12)
+            .for_client(&InitializeArguments::default());
+        assert_eq!(body.type_field, None);
+        assert_eq!(body.variables_reference, Some(12));
+    }
+
+    #[test]
+    fn test_error_with_message() {
+        let request = Request {
+            seq: 11,
+            command: crate::requests::Command::Threads,
+        };
+        let error = Message::new(1001, "Cannot list the threads of {process}")
+            .with_variable("process", "lua")
+            .with_show_user(true);
+        let response = Response::error_with(&request, error);
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({
+                "request_seq": 11,
+                "success": false,
+                "message": "Cannot list the threads of lua",
+                "command": "threads",
+                "body": {
+                    "error": {
+                        "id": 1001,
+                        "format": "Cannot list the threads of {process}",
+                        "variables": { "process": "lua" },
+                        "showUser": true
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_with_round_trip() {
+        // `continue` responses only have optional fields, the error must not be taken for one
+        let request = Request {
+            seq: 12,
+            command: crate::requests::Command::Continue(Default::default()),
+        };
+        let response = Response::error_with(&request, Message::new(4, "Thread is gone"));
+        let json = serde_json::to_string(&response).unwrap();
+        let decoded: Response = serde_json::from_str(&json).unwrap();
+        assert!(!decoded.success);
+        let Some(ResponseBody::Custom(CustomResponse { command, body })) = decoded.body else {
+            panic!("expected the error body to be kept");
+        };
+        assert_eq!(command, "continue");
+        let error: ErrorResponseBody = serde_json::from_value(body.unwrap()).unwrap();
+        assert_eq!(error.error.unwrap().id, 4);
+
+        let success: Response = serde_json::from_str(
+            r#"{"request_seq": 12, "success": true, "command": "continue", "body": {}}"#,
+        )
+        .unwrap();
+        assert!(matches!(success.body, Some(ResponseBody::Continue(_))));
+    }
 }
@@ -1,8 +1,11 @@
-use std::collections::HashMap;
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
 
 use serde::{Deserialize, Serialize};
 
-use crate::types::{RunInTerminalRequestArgumentsKind, StartDebuggingRequestKind};
+use crate::{
+    requests::InitializeArguments,
+    types::{RunInTerminalRequestArgumentsKind, StartDebuggingRequestKind},
+};
 
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -19,16 +22,57 @@ pub struct RunInTerminalRequestArguments {
     pub args: Vec<String>,
     /// Environment key-value pairs that are added to or removed from the default
     /// environment.
-    pub env: Option<HashMap<String, Option<String>>>,
+    pub env: Option<BTreeMap<String, Option<String>>>,
     /// This property should only be set if the corresponding capability
     /// `supportsArgsCanBeInterpretedByShell` is true. If the client uses an
     /// intermediary shell to launch the application, then the client must not
     /// attempt to escape characters with special meanings for the shell. The user
     /// is fully responsible for escaping as needed and that arguments using
     /// special characters may not be portable across shells.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub args_can_be_interpreted_by_shell: Option<bool>,
 }
 
+impl RunInTerminalRequestArguments {
+    /// Run `args` (the first one being the command) in `cwd`.
+    pub fn new(cwd: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            cwd: cwd.into(),
+            args,
+            ..Default::default()
+        }
+    }
+
+    /// Set what kind of terminal to launch.
+    pub fn with_kind(mut self, kind: RunInTerminalRequestArgumentsKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Set the title of the terminal.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Set `argsCanBeInterpretedByShell`, but only if the client announced
+    /// `supportsArgsCanBeInterpretedByShell` in its `initialize` request. Otherwise the attribute
+    /// is left out, since the client would ignore it anyway.
+    pub fn with_args_can_be_interpreted_by_shell(
+        mut self,
+        value: bool,
+        client: &InitializeArguments,
+    ) -> Self {
+        self.args_can_be_interpreted_by_shell =
+            if client.supports_args_can_be_interpreted_by_shell == Some(true) {
+                Some(value)
+            } else {
+                None
+            };
+        self
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StartDebuggingRequestArguments {
@@ -36,7 +80,7 @@ pub struct StartDebuggingRequestArguments {
     /// properties understood by the `launch` or `attach` requests of the debug
     /// adapter and they must not contain any client-specific properties (e.g.
     /// `type`) or client-specific features (e.g. substitutable 'variables').
-    pub configuration: HashMap<String, serde_json::Value>,
+    pub configuration: BTreeMap<String, serde_json::Value>,
     /// Indicates whether the new debug session should be started with a `launch`
     /// or `attach` request.
     /// Values: 'launch', 'attach'
@@ -98,6 +142,10 @@ pub struct ReverseRequest {
     /// associate requests with their corresponding responses. For protocol
     /// messages of type `request` the sequence number can be used to cancel the
     /// request.
+    ///
+    /// When sending, the server assigns the sequence number of the enclosing message instead, and
+    /// returns it from `send_reverse_request`.
+    #[serde(skip_serializing)]
     pub seq: i64,
     /// The command to execute.
     ///
@@ -106,3 +154,26 @@ pub struct ReverseRequest {
     #[serde(flatten)]
     pub command: ReverseCommand,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args_can_be_interpreted_by_shell_follows_client() {
+        let mut client = InitializeArguments {
+            adapter_id: "test".to_string(),
+            ..Default::default()
+        };
+        let args =
+            RunInTerminalRequestArguments::new("/tmp", vec!["lua".into(), "main.lua".into()])
+                .with_args_can_be_interpreted_by_shell(true, &client);
+        let val = serde_json::to_value(&args).unwrap();
+        assert!(val.get("argsCanBeInterpretedByShell").is_none());
+
+        client.supports_args_can_be_interpreted_by_shell = Some(true);
+        let args = args.with_args_can_be_interpreted_by_shell(true, &client);
+        let val = serde_json::to_value(&args).unwrap();
+        assert_eq!(val["argsCanBeInterpretedByShell"], true);
+    }
+}
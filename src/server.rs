@@ -1,29 +1,186 @@
+use std::any::Any;
+use std::collections::HashSet;
 use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::{Arc, Mutex};
 
-use serde_json;
+use serde::Serialize;
+use serde_json::{self, Value};
 
 use crate::{
-    base_message::{BaseMessage, Sendable},
+    base_message::{BaseMessage, IncomingMessage, Sendable},
+    cancellation::CancellationRegistry,
     errors::{DeserializationError, ServerError},
-    events::Event,
-    requests::Request,
-    responses::Response,
+    events::{Event, InvalidatedEventBody, OutputEventBody, StoppedEventBody},
+    requests::{CancelArguments, Command, InitializeArguments, Request},
+    responses::{Response, ResponseBody},
     reverse_requests::ReverseRequest,
+    types::{Capabilities, OutputEventCategory, StoppedEventReason},
 };
 
+/// The default limit for the size of incoming messages, see [`Server::max_message_size`].
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// A hook that is run on every decoded request by [`Server::poll_validated`].
+///
+/// Returning `Err` with a message rejects the request: an error response carrying the message
+/// is sent to the client and the request is not handed to the adapter.
+pub type RequestValidator = Box<dyn Fn(&Request) -> Result<(), String> + Send>;
+
+/// A hook that is told about protocol irregularities [`Server::poll_request`] recovered from,
+/// e.g. for logging them.
+pub type WarningHandler = Box<dyn Fn(&str) + Send>;
+
 /// Handles message encoding and decoding of messages.
 ///
 /// The `Server` is responsible for reading the incoming bytestream and constructing deserialized
 /// requests from it, as well as constructing and serializing outgoing messages.
 pub struct Server<R: Read, W: Write> {
     input_buffer: BufReader<R>,
+    validator: Option<RequestValidator>,
+    warning_handler: Option<WarningHandler>,
+    negotiated: Option<Negotiated>,
+    cancellations: CancellationRegistry,
+    frames: FrameReader,
 
     /// A sharable `ServerOutput` object for sending messages and events from
     /// other threads.
     pub output: Arc<Mutex<ServerOutput<W>>>,
 }
 
+/// Holds back a `stopped` event until the stack of the stopped thread can be served.
+///
+/// Clients usually request `stackTrace` as soon as they see `stopped`. An adapter that prepares
+/// its frames asynchronously hands the event to [`StopBarrier::stop`] when the debuggee stops,
+/// and calls [`StopBarrier::stack_ready`] (e.g. from the thread collecting the frames) once the
+/// stack is in place, which sends the event. Clones share the same pending events.
+pub struct StopBarrier<W: Write> {
+    output: Arc<Mutex<ServerOutput<W>>>,
+    /// At most one pending stop per `thread_id`, in the order the threads stopped.
+    pending: Arc<Mutex<Vec<StoppedEventBody>>>,
+}
+
+impl<W: Write> Clone for StopBarrier<W> {
+    fn clone(&self) -> Self {
+        Self {
+            output: Arc::clone(&self.output),
+            pending: Arc::clone(&self.pending),
+        }
+    }
+}
+
+impl<W: Write> StopBarrier<W> {
+    /// Hold `body` back until [`StopBarrier::stack_ready`]. A stop of the same thread that is
+    /// still pending is replaced, as the client only needs to know about the latest one; stops
+    /// of other threads are kept.
+    pub fn stop(&self, body: StoppedEventBody) -> Result<(), ServerError> {
+        let mut pending = self
+            .pending
+            .lock()
+            .map_err(|_| ServerError::OutputLockError)?;
+        match pending
+            .iter_mut()
+            .find(|stop| stop.thread_id == body.thread_id)
+        {
+            Some(stop) => *stop = body,
+            None => pending.push(body),
+        }
+        Ok(())
+    }
+
+    /// Send the pending `stopped` events in the order the threads stopped, see
+    /// [`ServerOutput::send_stopped`]. Returns whether there were any.
+    ///
+    /// A stop that is rejected doesn't hold back the others: they are all sent, and the first
+    /// error is returned afterwards.
+    pub fn stack_ready(&self) -> Result<bool, ServerError> {
+        let stops = core::mem::take(
+            &mut *self
+                .pending
+                .lock()
+                .map_err(|_| ServerError::OutputLockError)?,
+        );
+        if stops.is_empty() {
+            return Ok(false);
+        }
+        let mut output = self
+            .output
+            .lock()
+            .map_err(|_| ServerError::OutputLockError)?;
+        let mut result = Ok(true);
+        for body in stops {
+            if let Err(error) = output.send_stopped(body)
+                && result.is_ok()
+            {
+                result = Err(error);
+            }
+        }
+        result
+    }
+}
+
+/// The outcome of the `initialize` handshake: what the adapter advertised and what the client
+/// announced about itself.
+///
+/// Recorded by [`Server::complete_initialize`] and available through [`Server::negotiated`].
+#[derive(Debug, Default, Clone)]
+pub struct Negotiated {
+    /// The capabilities the adapter answered the `initialize` request with.
+    pub adapter: Capabilities,
+    /// The arguments of the client's `initialize` request.
+    pub client: InitializeArguments,
+}
+
+impl Negotiated {
+    /// Whether the adapter advertised the capability selected by `capability`.
+    pub fn adapter_supports(&self, capability: impl FnOnce(&Capabilities) -> Option<bool>) -> bool {
+        capability(&self.adapter) == Some(true)
+    }
+
+    /// Whether the client announced the feature selected by `feature`.
+    pub fn client_supports(
+        &self,
+        feature: impl FnOnce(&InitializeArguments) -> Option<bool>,
+    ) -> bool {
+        feature(&self.client) == Some(true)
+    }
+
+    /// Whether the client shows the `type` of variables and evaluate results.
+    pub fn supports_variable_type(&self) -> bool {
+        self.client_supports(|client| client.supports_variable_type)
+    }
+
+    /// Whether the client understands memory references.
+    pub fn supports_memory_references(&self) -> bool {
+        self.client_supports(|client| client.supports_memory_references)
+    }
+
+    /// Whether the adapter may send the `runInTerminal` reverse request.
+    pub fn supports_run_in_terminal_request(&self) -> bool {
+        self.client_supports(|client| client.supports_run_in_terminal_request)
+    }
+
+    /// Whether the adapter may send progress events.
+    pub fn supports_progress_reporting(&self) -> bool {
+        self.client_supports(|client| client.supports_progress_reporting)
+    }
+
+    /// Whether the client refreshes its views on `invalidated` events.
+    pub fn supports_invalidated_event(&self) -> bool {
+        self.client_supports(|client| client.supports_invalidated_event)
+    }
+
+    /// Whether lines sent by the client are 1-based (the default).
+    pub fn lines_start_at1(&self) -> bool {
+        self.client.lines_start_at1 != Some(false)
+    }
+
+    /// Whether columns sent by the client are 1-based (the default).
+    pub fn columns_start_at1(&self) -> bool {
+        self.client.columns_start_at1 != Some(false)
+    }
+}
+
 /// Handles emission of messages through the connection.
 ///
 /// `ServerOutput` is responsible for sending messages to the connection.
@@ -33,87 +190,260 @@ pub struct Server<R: Read, W: Write> {
 pub struct ServerOutput<W: Write> {
     output_buffer: BufWriter<W>,
     sequence_number: i64,
+    breakpoint_ids: HashSet<i64>,
+    next_breakpoint_id: i64,
+    buffer_early_events: bool,
+    validate_messages: bool,
+    initialize_responded: bool,
+    /// The `seq` of the `initialize` request read by the server, if any.
+    initialize_seq: Option<i64>,
+    early_events: Vec<Event>,
+    encoder: FrameEncoder,
 }
 
+/// The capacity of a [`FrameEncoder`]'s buffer that is kept between messages; the memory for
+/// larger messages is released after sending them.
+const RETAINED_SCRATCH_CAPACITY: usize = 1024 * 1024;
+
 impl<R: Read, W: Write> Server<R, W> {
     /// Construct a new Server using the given input and output streams.
     pub fn new(input: BufReader<R>, output: BufWriter<W>) -> Self {
         let server_output = Arc::new(Mutex::new(ServerOutput {
             output_buffer: output,
             sequence_number: 0,
+            breakpoint_ids: HashSet::new(),
+            next_breakpoint_id: 1,
+            buffer_early_events: false,
+            validate_messages: false,
+            initialize_responded: false,
+            initialize_seq: None,
+            early_events: Vec::new(),
+            encoder: FrameEncoder::default(),
         }));
 
         Self {
             input_buffer: input,
+            validator: None,
+            warning_handler: None,
+            negotiated: None,
+            cancellations: CancellationRegistry::new(),
+            frames: FrameReader::default(),
             output: server_output,
         }
     }
 
+    /// Install a hook that is told about protocol irregularities the server recovered from.
+    pub fn set_warning_handler(&mut self, handler: WarningHandler) {
+        self.warning_handler = Some(handler);
+    }
+
+    /// Whether blank lines before the first header of a message are skipped (the default).
+    ///
+    /// Some clients send an extra blank line before their first message, or after a body. With
+    /// skipping turned off such a line is a [`ServerError::ProtocolError`]. Blank lines within
+    /// a message always end its header part.
+    pub fn skip_leading_blanks(&mut self, skip: bool) {
+        self.frames.skip_leading_blanks = skip;
+    }
+
+    /// Whether headers other than `Content-Length` and `Content-Type` are rejected with
+    /// [`ServerError::UnknownHeader`]. By default they are ignored.
+    pub fn strict_headers(&mut self, strict: bool) {
+        self.frames.set_strict_headers(strict);
+    }
+
+    /// Limit the `Content-Length` of incoming messages to `limit` bytes, by default
+    /// [`DEFAULT_MAX_MESSAGE_SIZE`]. Longer messages are rejected with
+    /// [`ServerError::MessageTooLarge`] before anything is allocated for them.
+    pub fn max_message_size(&mut self, limit: usize) {
+        self.frames.set_max_message_size(limit);
+    }
+
+    /// The registry of cancelled requests. `cancel` requests read by [`Server::poll_request`]
+    /// are recorded in it, before the request is returned to the adapter.
+    pub fn cancellations(&self) -> CancellationRegistry {
+        self.cancellations.clone()
+    }
+
+    /// Install a hook that validates requests returned by [`Server::poll_validated`].
+    pub fn set_validator(&mut self, validator: RequestValidator) {
+        self.validator = Some(validator);
+    }
+
     /// Wait for a request from the development tool
     ///
     /// This will start reading the `input` buffer that is passed to it and will try to interpret
-    /// the incoming bytes according to the DAP protocol.
+    /// the incoming bytes according to the DAP protocol. A response from the client is reported as
+    /// [`ServerError::UnexpectedMessage`]; use [`Server::poll_message`] when the adapter sends
+    /// reverse requests.
+    ///
+    /// With a non-blocking input, or one with a read timeout, the `WouldBlock` or `TimedOut`
+    /// error is returned as a [`ServerError::IoError`]. The part of the message read so far is
+    /// kept, so calling this again once more input is available continues with it.
     pub fn poll_request(&mut self) -> Result<Option<Request>, ServerError> {
-        let mut header_buffer = String::new();
-        let mut content_length: usize = 0;
+        self.poll_message()?.map(expect_request).transpose()
+    }
+
+    /// Wait for a request like [`Server::poll_request`], but keep failures to decode a single
+    /// message apart from failures to read the connection.
+    ///
+    /// The outer error is fatal for the session. The inner one (such as a
+    /// [`ServerError::ParseError`] carrying the raw content) only concerns the message that was
+    /// just read: its framing was consumed in full, so the next call reads the next message.
+    pub fn poll_request_lossy(
+        &mut self,
+    ) -> Result<Option<Result<Request, ServerError>>, ServerError> {
+        let Some(content) = self.read_content()? else {
+            return Ok(None);
+        };
+        Ok(Some(self.accept(&content).and_then(expect_request)))
+    }
+
+    /// Wait for a message from the development tool.
+    ///
+    /// Unlike [`Server::poll_request`] this also accepts the client's responses to reverse
+    /// requests, so an adapter that sends e.g. `runInTerminal` can read the reply.
+    pub fn poll_message(&mut self) -> Result<Option<IncomingMessage>, ServerError> {
+        let Some(content) = self.read_content()? else {
+            return Ok(None);
+        };
+        self.accept(&content).map(Some)
+    }
+
+    /// Decode the content of a message just read.
+    fn accept(&self, content: &[u8]) -> Result<IncomingMessage, ServerError> {
+        let message = receive(content, &self.cancellations)?;
+        if let IncomingMessage::Request(request) = &message
+            && let Command::Initialize(_) = request.command
+        {
+            self.output
+                .lock()
+                .map_err(|_| ServerError::OutputLockError)?
+                .initialize_seq = Some(request.seq);
+        }
+        Ok(message)
+    }
+
+    /// Read the headers and the content of the next message, `None` on EOF.
+    fn read_content(&mut self) -> Result<Option<Vec<u8>>, ServerError> {
+        let warning_handler = &self.warning_handler;
+        read_frame(&mut self.input_buffer, &mut self.frames, || {
+            if let Some(warn) = warning_handler {
+                warn("skipped blank lines before a message header");
+            }
+        })
+    }
 
-        // Parse headers until we get an empty line
+    /// Wait for a request that passes the validator installed with [`Server::set_validator`].
+    ///
+    /// Requests rejected by the validator are answered with an error response and skipped, so
+    /// this only returns requests the adapter actually has to handle. Without a validator this
+    /// behaves exactly like [`Server::poll_request`].
+    pub fn poll_validated(&mut self) -> Result<Option<Request>, ServerError> {
         loop {
-            header_buffer.clear();
-            let bytes_read = self
-                .input_buffer
-                .read_line(&mut header_buffer)
-                .map_err(ServerError::IoError)?;
-
-            if bytes_read == 0 {
-                return Ok(None); // EOF
-            }
-
-            let trimmed = header_buffer.trim_end();
-
-            // Empty line signals end of headers
-            if trimmed.is_empty() {
-                break;
-            }
-
-            // Parse "Header-Name: value" format
-            if let Some(colon_pos) = trimmed.find(':') {
-                let (header_name, header_value) = trimmed.split_at(colon_pos);
-                match header_name {
-                    "Content-Length" => {
-                        content_length = header_value[1..] // Skip the ':'
-                            .trim()
-                            .parse()
-                            .map_err(|_| ServerError::HeaderParseError {
-                                line: header_buffer.clone(),
-                            })?;
-                    }
-                    other => {
-                        return Err(ServerError::UnknownHeader {
-                            header: other.to_string(),
-                        });
-                    }
-                }
-            } else {
-                return Err(ServerError::HeaderParseError {
-                    line: header_buffer,
-                });
+            let Some(request) = self.poll_request()? else {
+                return Ok(None);
+            };
+            let verdict = match &self.validator {
+                Some(validator) => validator(&request),
+                None => Ok(()),
+            };
+            match verdict {
+                Ok(()) => return Ok(Some(request)),
+                Err(message) => self.respond(request.error(&message))?,
             }
         }
+    }
 
-        // Read content
-        let mut content = vec![0u8; content_length];
-        self.input_buffer
-            .read_exact(&mut content)
-            .map_err(ServerError::IoError)?;
-
-        let content_str = std::str::from_utf8(&content)
-            .map_err(|e| ServerError::ParseError(DeserializationError::DecodingError(e)))?;
+    /// Run `handler` for `request` and send the response it produces.
+    ///
+    /// A handler returns `None` for requests it does not handle (such as
+    /// [`Command::Custom`] requests it does not know); the client then receives an error
+    /// response naming the command, so it is never left waiting for an answer.
+    ///
+    /// If the handler panics, the panic is caught and the client receives an error response for
+    /// the request, plus an `output` event on the console describing the panic. This keeps the
+    /// session alive (and the client from waiting forever) through bugs in the adapter.
+    ///
+    /// Once [`Server::complete_initialize`] has run, another `initialize` request and requests
+    /// that depend on a capability the adapter did not advertise (see
+    /// [`Command::missing_capability`]) are rejected with an error response without calling
+    /// `handler`.
+    pub fn dispatch<F>(&mut self, request: Request, handler: F) -> Result<(), ServerError>
+    where
+        F: FnOnce(Request) -> Option<Response>,
+    {
+        if self.negotiated.is_some() && matches!(request.command, Command::Initialize(_)) {
+            return self.respond(Response::error_for(
+                &request,
+                "the debug session has already been initialized",
+            ));
+        }
+        if let Some(negotiated) = &self.negotiated
+            && let Some(capability) = request.command.missing_capability(&negotiated.adapter)
+        {
+            let message = format!("request is not supported by this debug adapter ({capability})");
+            return self.respond(Response::error_for(&request, message));
+        }
+        let request_seq = request.seq;
+        let command = request.command.name().to_string();
+        match panic::catch_unwind(AssertUnwindSafe(|| handler(request))) {
+            Ok(Some(response)) => self.respond(response),
+            Ok(None) => {
+                let error = crate::handler::unsupported(&command);
+                self.respond(Response::structured_error(
+                    request_seq,
+                    &command,
+                    error.formatted(),
+                    error,
+                ))
+            }
+            Err(payload) => {
+                let reason = panic_message(payload.as_ref());
+                self.respond(Response::command_error(
+                    request_seq,
+                    &command,
+                    format!("internal error in debug adapter: {reason}"),
+                ))?;
+                self.send_event(Event::Output(OutputEventBody {
+                    category: Some(OutputEventCategory::Console),
+                    output: format!(
+                        "debug adapter panicked while handling request {request_seq}: {reason}\n"
+                    ),
+                    ..Default::default()
+                }))
+            }
+        }
+    }
 
-        let request: Request = serde_json::from_str(content_str)
-            .map_err(|e| ServerError::ParseError(DeserializationError::SerdeError(e)))?;
+    /// Answer the `initialize` request and send the `initialized` event. See
+    /// [`ServerOutput::complete_initialize`]. The output lock is held for both messages, so no
+    /// other message can end up in between.
+    ///
+    /// On success the handshake is recorded and can be queried via [`Server::negotiated`].
+    pub fn complete_initialize(
+        &mut self,
+        request: Request,
+        capabilities: Capabilities,
+    ) -> Result<(), ServerError> {
+        let client = match &request.command {
+            Command::Initialize(arguments) => arguments.clone(),
+            _ => return Err(ServerError::ResponseConstructError),
+        };
+        let adapter = capabilities.clone();
+        let mut output = self
+            .output
+            .lock()
+            .map_err(|_| ServerError::OutputLockError)?;
+        output.complete_initialize(request, capabilities)?;
+        self.negotiated = Some(Negotiated { adapter, client });
+        Ok(())
+    }
 
-        Ok(Some(request))
+    /// The capabilities negotiated by [`Server::complete_initialize`]. Before the handshake has
+    /// completed nothing is considered supported.
+    pub fn negotiated(&self) -> Negotiated {
+        self.negotiated.clone().unwrap_or_default()
     }
 
     pub fn send(&mut self, body: Sendable) -> Result<(), ServerError> {
@@ -124,6 +454,18 @@ impl<R: Read, W: Write> Server<R, W> {
         output.send(body)
     }
 
+    /// See [`ServerOutput::send_all`].
+    pub fn send_all(
+        &mut self,
+        bodies: impl IntoIterator<Item = Sendable>,
+    ) -> Result<(), ServerError> {
+        let mut output = self
+            .output
+            .lock()
+            .map_err(|_| ServerError::OutputLockError)?;
+        output.send_all(bodies)
+    }
+
     pub fn respond(&mut self, response: Response) -> Result<(), ServerError> {
         self.send(Sendable::Response(response))
     }
@@ -132,119 +474,1920 @@ impl<R: Read, W: Write> Server<R, W> {
         self.send(Sendable::Event(event))
     }
 
-    pub fn send_reverse_request(&mut self, request: ReverseRequest) -> Result<(), ServerError> {
-        self.send(Sendable::ReverseRequest(request))
+    /// Send a reverse request and return the `seq` assigned to it, which the client's response
+    /// carries as its `request_seq`.
+    pub fn send_reverse_request(&mut self, request: ReverseRequest) -> Result<i64, ServerError> {
+        let mut output = self
+            .output
+            .lock()
+            .map_err(|_| ServerError::OutputLockError)?;
+        output.send_reverse_request(request)
     }
-}
-
-impl<W: Write> ServerOutput<W> {
-    pub fn send(&mut self, body: Sendable) -> Result<(), ServerError> {
-        self.sequence_number += 1;
 
-        let message = BaseMessage {
-            seq: self.sequence_number,
-            message: body,
-        };
+    /// Prepare for a new run of the debuggee, e.g. when handling a `restart` request.
+    ///
+    /// State tied to the previous run (such as the allocated breakpoint ids, see
+    /// [`ServerOutput::reset_session`]) is cleared, while the capabilities negotiated during
+    /// `initialize` are kept: the client does not send another `initialize` request.
+    pub fn reset_session_keep_capabilities(&mut self) -> Result<(), ServerError> {
+        let mut output = self
+            .output
+            .lock()
+            .map_err(|_| ServerError::OutputLockError)?;
+        output.reset_session();
+        Ok(())
+    }
 
-        let resp_json = serde_json::to_string(&message).map_err(ServerError::SerializationError)?;
+    /// See [`ServerOutput::set_buffer_early_events`].
+    pub fn set_buffer_early_events(&mut self, enabled: bool) -> Result<(), ServerError> {
+        let mut output = self
+            .output
+            .lock()
+            .map_err(|_| ServerError::OutputLockError)?;
+        output.set_buffer_early_events(enabled);
+        Ok(())
+    }
 
-        // Write header and content in a single operation
-        write!(
-            self.output_buffer,
-            "Content-Length: {}\r\n\r\n{}",
-            resp_json.len(),
-            resp_json
-        )
-        .map_err(ServerError::IoError)?;
+    /// A [`StopBarrier`] sending its `stopped` events through this server's output.
+    pub fn stop_barrier(&self) -> StopBarrier<W> {
+        StopBarrier {
+            output: Arc::clone(&self.output),
+            pending: Arc::default(),
+        }
+    }
 
-        self.output_buffer.flush().map_err(ServerError::IoError)?;
+    /// See [`ServerOutput::set_validate_messages`].
+    pub fn set_validate_messages(&mut self, enabled: bool) -> Result<(), ServerError> {
+        let mut output = self
+            .output
+            .lock()
+            .map_err(|_| ServerError::OutputLockError)?;
+        output.set_validate_messages(enabled);
         Ok(())
     }
 
-    pub fn respond(&mut self, response: Response) -> Result<(), ServerError> {
-        self.send(Sendable::Response(response))
+    /// See [`ServerOutput::allocate_breakpoint_id`].
+    pub fn allocate_breakpoint_id(&mut self) -> Result<i64, ServerError> {
+        let mut output = self
+            .output
+            .lock()
+            .map_err(|_| ServerError::OutputLockError)?;
+        Ok(output.allocate_breakpoint_id())
     }
 
-    pub fn send_event(&mut self, event: Event) -> Result<(), ServerError> {
-        self.send(Sendable::Event(event))
+    /// See [`ServerOutput::send_stopped`].
+    pub fn send_stopped(&mut self, body: StoppedEventBody) -> Result<(), ServerError> {
+        let mut output = self
+            .output
+            .lock()
+            .map_err(|_| ServerError::OutputLockError)?;
+        output.send_stopped(body)
     }
 
-    pub fn send_reverse_request(&mut self, request: ReverseRequest) -> Result<(), ServerError> {
-        self.send(Sendable::ReverseRequest(request))
+    /// Tell the client to refetch the variables of `thread_id` (or of its frame
+    /// `stack_frame_id`) after a successful `setVariable` request, see
+    /// [`InvalidatedEventBody::variables`].
+    ///
+    /// Nothing is sent if the client did not announce `supportsInvalidatedEvent`.
+    pub fn send_variables_invalidated(
+        &mut self,
+        thread_id: i64,
+        stack_frame_id: Option<i64>,
+    ) -> Result<(), ServerError> {
+        if !self
+            .negotiated
+            .as_ref()
+            .is_some_and(Negotiated::supports_invalidated_event)
+        {
+            return Ok(());
+        }
+        self.send_event(Event::Invalidated(InvalidatedEventBody::variables(
+            thread_id,
+            stack_frame_id,
+        )))
     }
 }
 
-#[cfg(test)]
-mod tests {
+/// What a header line fed to a [`HeaderParser`] turned out to be.
+enum HeaderLine {
+    /// A header; more lines follow.
+    Header,
+    /// A blank line before any header, e.g. a newline trailing the previous message's body
+    /// that its Content-Length did not count. Skipped to stay in sync.
+    SkippedBlank,
+    /// The blank line ending the header part. The body follows.
+    End,
+}
 
-    use std::io::Cursor;
+/// Parses the header part of a message line by line.
+///
+/// Driven line by line by a [`FrameReader`].
+///
+/// Headers other than `Content-Length` and `Content-Type` are ignored, unless the parser is
+/// strict.
+struct HeaderParser {
+    /// The value of the `Content-Length` header, once it was seen.
+    content_length: usize,
+    saw_content_length: bool,
+    saw_header: bool,
+    strict: bool,
+    max_message_size: usize,
+}
 
-    use serde_json::Value;
+impl Default for HeaderParser {
+    fn default() -> Self {
+        Self::new(false, DEFAULT_MAX_MESSAGE_SIZE)
+    }
+}
 
-    use super::*;
-    use crate::requests::{AttachOrLaunchArguments, Command, RestartArguments};
+impl HeaderParser {
+    /// A parser that rejects unknown headers with [`ServerError::UnknownHeader`] if `strict`,
+    /// and messages longer than `max_message_size` with [`ServerError::MessageTooLarge`].
+    fn new(strict: bool, max_message_size: usize) -> Self {
+        Self {
+            content_length: 0,
+            saw_content_length: false,
+            saw_header: false,
+            strict,
+            max_message_size,
+        }
+    }
 
-    fn simulate_poll_request(input: &str) -> Request {
-        let mut server_in = Cursor::new(input.as_bytes().to_vec());
-        let server_out = Vec::new();
-        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(server_out));
+    fn feed(&mut self, line: &str) -> Result<HeaderLine, ServerError> {
+        let trimmed = line.trim_end();
 
-        server.poll_request().unwrap().unwrap()
+        // Empty line signals end of headers
+        if trimmed.is_empty() {
+            if !self.saw_header {
+                return Ok(HeaderLine::SkippedBlank);
+            }
+            if !self.saw_content_length {
+                return Err(ServerError::ProtocolError {
+                    reason: "missing Content-Length header".to_string(),
+                    line: line.to_string(),
+                });
+            }
+            return Ok(HeaderLine::End);
+        }
+        self.saw_header = true;
+
+        // Parse "Header-Name: value" format
+        let Some(colon_pos) = trimmed.find(':') else {
+            return Err(ServerError::HeaderParseError {
+                line: line.to_string(),
+            });
+        };
+        let (header_name, header_value) = trimmed.split_at(colon_pos);
+        match header_name {
+            "Content-Length" => {
+                self.content_length = header_value[1..] // Skip the ':'
+                    .trim()
+                    .parse()
+                    .map_err(|_| ServerError::HeaderParseError {
+                        line: line.to_string(),
+                    })?;
+                if self.content_length > self.max_message_size {
+                    return Err(ServerError::MessageTooLarge {
+                        length: self.content_length,
+                        limit: self.max_message_size,
+                    });
+                }
+                self.saw_content_length = true;
+            }
+            "Content-Type" => {
+                // The body is always decoded as UTF-8, so reject anything else up front
+                let charset = header_value[1..]
+                    .split(';')
+                    .skip(1)
+                    .filter_map(|param| param.trim().split_once('='))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case("charset"))
+                    .map(|(_, value)| value.trim().trim_matches('"'));
+                if let Some(charset) = charset
+                    && !charset.eq_ignore_ascii_case("utf-8")
+                    && !charset.eq_ignore_ascii_case("utf8")
+                {
+                    return Err(ServerError::UnsupportedCharset {
+                        charset: charset.to_string(),
+                    });
+                }
+            }
+            other if self.strict => {
+                return Err(ServerError::UnknownHeader {
+                    header: other.to_string(),
+                });
+            }
+            _ => {}
+        }
+        Ok(HeaderLine::Header)
     }
+}
 
-    #[test]
-    fn test_server_init_request() {
-        let req = simulate_poll_request(
-            "Content-Length: 155\r\n\r\n{\"seq\": 152,\"type\": \"request\",\"command\": \"initialize\",\"arguments\": {\"adapterID\": \"0001e357-72c7-4f03-ae8f-c5b54bd8dabf\", \"clientName\": \"Some Cool Editor\"}}",
-        );
+/// Decodes the framing of incoming messages, header lines followed by `Content-Length` bytes of
+/// content, from bytes as they arrive.
+///
+/// [`Server`], the async server and the [`Client`](crate::client::Client) all feed it what their
+/// reader has buffered, so they read the wire format exactly the same way.
+pub(crate) struct FrameReader {
+    /// Whether blank lines before the first header of a message are skipped or rejected.
+    pub(crate) skip_leading_blanks: bool,
+    headers: HeaderParser,
+    state: FrameState,
+}
 
-        assert_eq!(req.seq, 152);
-        assert!(matches!(req.command, Command::Initialize { .. }));
+enum FrameState {
+    /// Reading a header line, holding what arrived of it so far.
+    Header(Vec<u8>),
+    /// Reading the content, holding what arrived of it so far.
+    Content(Vec<u8>),
+}
+
+/// What [`FrameReader::advance`] made of its input.
+pub(crate) enum FrameProgress {
+    /// More input is needed.
+    Pending,
+    /// A blank line before the first header of a message was skipped.
+    SkippedBlank,
+    /// The content of a complete message.
+    Frame(Vec<u8>),
+}
+
+/// How much is allocated for the content of a message before any of it was read, so a bogus
+/// `Content-Length` doesn't allocate up front.
+const INITIAL_CONTENT_CAPACITY: usize = 8 * 1024;
+
+impl Default for FrameReader {
+    fn default() -> Self {
+        Self {
+            skip_leading_blanks: true,
+            headers: HeaderParser::default(),
+            state: FrameState::Header(Vec::new()),
+        }
     }
+}
 
-    #[test]
-    fn test_server_restart_request() {
-        let req = simulate_poll_request(
-            "Content-Length: 67\r\n\r\n{\"seq\": 152,\"type\": \"request\",\"command\": \"restart\",\"arguments\": {}}",
-        );
+impl FrameReader {
+    /// Reject headers other than `Content-Length` and `Content-Type` if `strict`.
+    pub(crate) fn set_strict_headers(&mut self, strict: bool) {
+        self.headers.strict = strict;
+    }
 
-        assert!(matches!(
-            req.command,
-            Command::Restart {
-                0: RestartArguments { arguments: None }
-            }
-        ));
+    /// Reject messages longer than `limit` bytes.
+    pub(crate) fn set_max_message_size(&mut self, limit: usize) {
+        self.headers.max_message_size = limit;
+    }
 
-        // Restarting a launch request
-        let req = simulate_poll_request(
-            "Content-Length: 96\r\n\r\n{\"seq\": 152,\"type\": \"request\",\"command\": \"restart\",\"arguments\": {\"arguments\": {\"noDebug\":true}}}",
-        );
-        assert!(matches!(
-            req.command,
-            Command::Restart {
-                0: RestartArguments {
-                    arguments: Some(AttachOrLaunchArguments {
-                        no_debug: Some(_),
-                        ..
-                    })
+    /// Whether part of a message has been read.
+    pub(crate) fn in_frame(&self) -> bool {
+        match &self.state {
+            FrameState::Header(line) => self.headers.saw_header || !line.is_empty(),
+            FrameState::Content(_) => true,
+        }
+    }
+
+    /// Consume a prefix of `input`, returning how many bytes were used and what they amounted
+    /// to. After an error the reader starts over with the next message.
+    pub(crate) fn advance(&mut self, input: &[u8]) -> (usize, Result<FrameProgress, ServerError>) {
+        let length = self.headers.content_length;
+        match &mut self.state {
+            FrameState::Header(line) => {
+                let Some(end) = input.iter().position(|&byte| byte == b'\n') else {
+                    line.extend_from_slice(input);
+                    return (input.len(), Ok(FrameProgress::Pending));
+                };
+                line.extend_from_slice(&input[..=end]);
+                let line = core::mem::take(line);
+                let progress = self.header_line(&line);
+                if progress.is_err() {
+                    self.reset();
                 }
+                (end + 1, progress)
             }
-        ));
-
-        // Restarting a launch or attach request
-        let req = simulate_poll_request(
-            "Content-Length: 98\r\n\r\n{\"seq\": 152,\"type\": \"request\",\"command\": \"restart\",\"arguments\": {\"arguments\": {\"__restart\":true}}}",
-        );
-        assert!(matches!(
-            req.command,
-            Command::Restart {
-                0: RestartArguments {
-                    arguments: Some(AttachOrLaunchArguments {
-                        restart_data: Some(Value::Bool(true)),
-                        ..
-                    })
+            FrameState::Content(content) => {
+                let used = (length - content.len()).min(input.len());
+                content.extend_from_slice(&input[..used]);
+                if content.len() < length {
+                    return (used, Ok(FrameProgress::Pending));
                 }
+                let content = core::mem::take(content);
+                self.reset();
+                (used, Ok(FrameProgress::Frame(content)))
             }
-        ));
+        }
+    }
+
+    /// Tell the reader its input ended. Fine between messages, an `UnexpectedEof` error within
+    /// one.
+    pub(crate) fn finish(&mut self) -> Result<(), ServerError> {
+        if !self.in_frame() {
+            return Ok(());
+        }
+        self.reset();
+        Err(ServerError::IoError(
+            std::io::ErrorKind::UnexpectedEof.into(),
+        ))
+    }
+
+    fn header_line(&mut self, line: &[u8]) -> Result<FrameProgress, ServerError> {
+        let line = core::str::from_utf8(line).map_err(|_| ServerError::HeaderParseError {
+            line: String::from_utf8_lossy(line).into_owned(),
+        })?;
+        match self.headers.feed(line)? {
+            HeaderLine::Header => Ok(FrameProgress::Pending),
+            HeaderLine::SkippedBlank if self.skip_leading_blanks => Ok(FrameProgress::SkippedBlank),
+            HeaderLine::SkippedBlank => Err(ServerError::ProtocolError {
+                reason: "blank line before the first header".to_string(),
+                line: line.to_string(),
+            }),
+            HeaderLine::End if self.headers.content_length == 0 => {
+                self.reset();
+                Ok(FrameProgress::Frame(Vec::new()))
+            }
+            HeaderLine::End => {
+                let capacity = self.headers.content_length.min(INITIAL_CONTENT_CAPACITY);
+                self.state = FrameState::Content(Vec::with_capacity(capacity));
+                Ok(FrameProgress::Pending)
+            }
+        }
+    }
+
+    /// Start over with the next message.
+    fn reset(&mut self) {
+        self.headers = HeaderParser::new(self.headers.strict, self.headers.max_message_size);
+        self.state = FrameState::Header(Vec::new());
+    }
+}
+
+/// Read the next message from `input` with `frames`, `None` on EOF.
+///
+/// `on_skipped_blank` is called for every blank line skipped before the first header. Shared by
+/// [`Server`] and the [`Client`](crate::client::Client).
+///
+/// Errors of the reader, such as `WouldBlock` or `TimedOut` from a socket with a read timeout,
+/// are returned as they are. What was read of a message so far stays in `frames`, so calling
+/// this again resumes the message where it stopped.
+pub(crate) fn read_frame<R: Read>(
+    input: &mut BufReader<R>,
+    frames: &mut FrameReader,
+    mut on_skipped_blank: impl FnMut(),
+) -> Result<Option<Vec<u8>>, ServerError> {
+    loop {
+        let available = match input.fill_buf() {
+            Ok(available) => available,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(ServerError::IoError(e)),
+        };
+        if available.is_empty() {
+            return frames.finish().map(|()| None);
+        }
+        let (used, progress) = frames.advance(available);
+        input.consume(used);
+        match progress? {
+            FrameProgress::Pending => {}
+            FrameProgress::SkippedBlank => on_skipped_blank(),
+            FrameProgress::Frame(content) => return Ok(Some(content)),
+        }
+    }
+}
+
+/// Decode the content of a message just read, and record it if it is a `cancel` request.
+///
+/// Shared by [`Server`] and the async server.
+pub(crate) fn receive(
+    content: &[u8],
+    cancellations: &CancellationRegistry,
+) -> Result<IncomingMessage, ServerError> {
+    let message = decode_message(content)?;
+    #[cfg(feature = "tracing")]
+    trace_received(&message, content);
+    if let IncomingMessage::Request(request) = &message {
+        record_cancellation(request, cancellations);
+    }
+    Ok(message)
+}
+
+/// Decode the body of a message into a request or a response, depending on its `type`.
+pub(crate) fn decode_message(content: &[u8]) -> Result<IncomingMessage, ServerError> {
+    let value = decode_json(content)?;
+    let invalid = |e| parse_error(content, DeserializationError::SerdeError(e));
+    match value.get("type").and_then(Value::as_str) {
+        Some("request") => serde_json::from_value(value)
+            .map(IncomingMessage::Request)
+            .map_err(invalid),
+        Some("response") => serde_json::from_value(value)
+            .map(IncomingMessage::Response)
+            .map_err(invalid),
+        found => Err(ServerError::UnexpectedMessage {
+            expected: "request or response".to_string(),
+            found: found.unwrap_or("untyped").to_string(),
+        }),
+    }
+}
+
+/// Parse the body of a message as JSON.
+pub(crate) fn decode_json(content: &[u8]) -> Result<Value, ServerError> {
+    let content_str = std::str::from_utf8(content)
+        .map_err(|e| parse_error(content, DeserializationError::DecodingError(e)))?;
+    serde_json::from_str(content_str)
+        .map_err(|e| parse_error(content, DeserializationError::SerdeError(e)))
+}
+
+/// A [`ServerError::ParseError`] for the message `content`.
+pub(crate) fn parse_error(content: &[u8], source: DeserializationError) -> ServerError {
+    ServerError::ParseError {
+        content: String::from_utf8_lossy(content).into_owned(),
+        source,
+    }
+}
+
+fn expect_request(message: IncomingMessage) -> Result<Request, ServerError> {
+    match message {
+        IncomingMessage::Request(request) => Ok(request),
+        IncomingMessage::Response(_) => Err(ServerError::UnexpectedMessage {
+            expected: "request".to_string(),
+            found: "response".to_string(),
+        }),
+    }
+}
+
+/// Record the request and the progress a `cancel` request refers to in `cancellations`.
+pub(crate) fn record_cancellation(request: &Request, cancellations: &CancellationRegistry) {
+    if let Command::Cancel(CancelArguments {
+        request_id,
+        progress_id,
+    }) = &request.command
+    {
+        if let Some(request_id) = request_id {
+            cancellations.cancel(*request_id);
+        }
+        if let Some(progress_id) = progress_id {
+            cancellations.cancel_progress(progress_id);
+        }
+    }
+}
+
+/// Serializes outgoing messages into a buffer that is reused from one message to the next, to
+/// avoid an allocation per message.
+///
+/// Everything that writes messages (both servers and the [`Client`](crate::client::Client))
+/// frames them with this, so they agree on the wire format.
+#[derive(Default)]
+pub(crate) struct FrameEncoder {
+    scratch: Vec<u8>,
+}
+
+/// The `Content-Length` header of an encoded message, including the blank line ending it.
+pub(crate) struct FrameHeader {
+    bytes: [u8; FRAME_HEADER_CAPACITY],
+    len: usize,
+}
+
+/// Room for `Content-Length: ` and `\r\n\r\n` around the longest `usize`.
+const FRAME_HEADER_CAPACITY: usize = 48;
+
+impl FrameHeader {
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+impl FrameEncoder {
+    /// Serialize `message`, returning its header and its content, to be written in that order.
+    pub(crate) fn encode(
+        &mut self,
+        message: &impl Serialize,
+    ) -> Result<(FrameHeader, &[u8]), ServerError> {
+        self.scratch.clear();
+        serde_json::to_writer(&mut self.scratch, message)
+            .map_err(ServerError::SerializationError)?;
+
+        let mut bytes = [0; FRAME_HEADER_CAPACITY];
+        let mut rest = &mut bytes[..];
+        write!(rest, "Content-Length: {}\r\n\r\n", self.scratch.len())
+            .expect("the header of any length fits");
+        let len = FRAME_HEADER_CAPACITY - rest.len();
+        Ok((FrameHeader { bytes, len }, &self.scratch))
+    }
+
+    /// Release the buffer if a large message made it grow past [`RETAINED_SCRATCH_CAPACITY`].
+    pub(crate) fn trim(&mut self) {
+        if self.scratch.capacity() > RETAINED_SCRATCH_CAPACITY {
+            self.scratch = Vec::new();
+        }
+    }
+}
+
+/// Log a received message: its command and `seq` at debug level, its content at trace level.
+#[cfg(feature = "tracing")]
+pub(crate) fn trace_received(message: &IncomingMessage, content: &[u8]) {
+    match message {
+        IncomingMessage::Request(request) => {
+            tracing::debug!(
+                command = request.command.name(),
+                seq = request.seq,
+                "received request"
+            );
+        }
+        IncomingMessage::Response(response) => {
+            tracing::debug!(
+                request_seq = response.request_seq,
+                success = response.success,
+                "received response"
+            );
+        }
+    }
+    tracing::trace!(content = %String::from_utf8_lossy(content), "received message");
+}
+
+/// Log a message about to be sent, with the JSON it was serialized to at trace level.
+#[cfg(feature = "tracing")]
+pub(crate) fn trace_sent(message: &BaseMessage, content: &[u8]) {
+    let kind = match message.message {
+        Sendable::Response(_) => "response",
+        Sendable::Event(_) => "event",
+        Sendable::ReverseRequest(_) => "reverse request",
+    };
+    tracing::debug!(kind, seq = message.seq, "sending message");
+    tracing::trace!(content = %String::from_utf8_lossy(content), "sending message");
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+impl<W: Write> ServerOutput<W> {
+    /// Hold back events sent before the `initialize` response.
+    ///
+    /// The protocol does not allow events ahead of the `initialize` response. When enabled,
+    /// events (other than `initialized`) sent too early are queued and written right after the
+    /// response, in the order they were sent. This holds for an error response to `initialize`
+    /// too, so the queued events aren't held back for the rest of the session.
+    pub fn set_buffer_early_events(&mut self, enabled: bool) {
+        self.buffer_early_events = enabled;
+    }
+
+    /// Check every message with [`Sendable::validate`] before sending it, failing with
+    /// [`ServerError::InvalidMessage`] instead of sending an invalid one. Off by default.
+    pub fn set_validate_messages(&mut self, enabled: bool) {
+        self.validate_messages = enabled;
+    }
+
+    pub fn send(&mut self, body: Sendable) -> Result<(), ServerError> {
+        self.enqueue(body)?;
+        self.output_buffer.flush().map_err(ServerError::IoError)
+    }
+
+    /// Send several messages, only flushing the connection once after the last one.
+    pub fn send_all(
+        &mut self,
+        bodies: impl IntoIterator<Item = Sendable>,
+    ) -> Result<(), ServerError> {
+        for body in bodies {
+            self.enqueue(body)?;
+        }
+        self.output_buffer.flush().map_err(ServerError::IoError)
+    }
+
+    fn enqueue(&mut self, body: Sendable) -> Result<(), ServerError> {
+        if self.validate_messages {
+            body.validate()?;
+        }
+        match body {
+            Sendable::Event(event)
+                if self.buffer_early_events
+                    && !self.initialize_responded
+                    && !matches!(event, Event::Initialized) =>
+            {
+                self.early_events.push(event);
+                Ok(())
+            }
+            Sendable::Response(response)
+                if !self.initialize_responded && self.answers_initialize(&response) =>
+            {
+                self.initialize_responded = true;
+                self.write(Sendable::Response(response))?;
+                for event in std::mem::take(&mut self.early_events) {
+                    self.write(Sendable::Event(event))?;
+                }
+                Ok(())
+            }
+            body => self.write(body),
+        }
+    }
+
+    /// Whether `response` is the response to the `initialize` request, successful or not.
+    fn answers_initialize(&self, response: &Response) -> bool {
+        match &response.body {
+            Some(ResponseBody::Initialize(_)) => true,
+            Some(ResponseBody::Custom(custom)) => custom.command == "initialize",
+            _ => self.initialize_seq == Some(response.request_seq),
+        }
+    }
+
+    fn write(&mut self, body: Sendable) -> Result<(), ServerError> {
+        self.sequence_number += 1;
+        let message = BaseMessage {
+            seq: self.sequence_number,
+            message: body,
+        };
+        let (header, content) = self.encoder.encode(&message)?;
+        #[cfg(feature = "tracing")]
+        trace_sent(&message, content);
+
+        let result = self
+            .output_buffer
+            .write_all(header.as_bytes())
+            .and_then(|()| self.output_buffer.write_all(content))
+            .map_err(ServerError::IoError);
+        self.encoder.trim();
+        result
+    }
+
+    pub fn respond(&mut self, response: Response) -> Result<(), ServerError> {
+        self.send(Sendable::Response(response))
+    }
+
+    pub fn send_event(&mut self, event: Event) -> Result<(), ServerError> {
+        self.send(Sendable::Event(event))
+    }
+
+    /// Send a reverse request and return the `seq` assigned to it, which the client's response
+    /// carries as its `request_seq`.
+    pub fn send_reverse_request(&mut self, request: ReverseRequest) -> Result<i64, ServerError> {
+        self.send(Sendable::ReverseRequest(request))?;
+        Ok(self.sequence_number)
+    }
+
+    /// Forget the breakpoint ids handed out so far. Ids are still never reused, so late events
+    /// from a previous run cannot be confused with breakpoints of the new one.
+    pub fn reset_session(&mut self) {
+        self.breakpoint_ids.clear();
+    }
+
+    /// Hand out a new, unique id for a breakpoint reported to the client.
+    pub fn allocate_breakpoint_id(&mut self) -> i64 {
+        let id = self.next_breakpoint_id;
+        self.next_breakpoint_id += 1;
+        self.breakpoint_ids.insert(id);
+        id
+    }
+
+    /// Send a `stopped` event.
+    ///
+    /// Returns [`ServerError::UnknownBreakpointId`] without sending anything if one of the
+    /// `hitBreakpointIds` was not handed out by [`ServerOutput::allocate_breakpoint_id`], and
+    /// [`ServerError::InvalidEvent`] if `text` is set for a stop that is not an exception.
+    pub fn send_stopped(&mut self, body: StoppedEventBody) -> Result<(), ServerError> {
+        if body.text.is_some() && !matches!(body.reason, StoppedEventReason::Exception) {
+            return Err(ServerError::InvalidEvent {
+                reason: "only exception stops carry a text".to_string(),
+            });
+        }
+        if let Some(&id) = body
+            .hit_breakpoint_ids
+            .iter()
+            .flatten()
+            .find(|id| !self.breakpoint_ids.contains(id))
+        {
+            return Err(ServerError::UnknownBreakpointId { id });
+        }
+        self.send_event(Event::Stopped(body))
+    }
+
+    /// Answer the `initialize` request with `capabilities` and then send the `initialized`
+    /// event, in that order.
+    ///
+    /// Returns [`ServerError::ResponseConstructError`] if `request` is not an `initialize`
+    /// request.
+    pub fn complete_initialize(
+        &mut self,
+        request: Request,
+        capabilities: Capabilities,
+    ) -> Result<(), ServerError> {
+        if !matches!(request.command, Command::Initialize(_)) {
+            return Err(ServerError::ResponseConstructError);
+        }
+        self.respond(request.success(ResponseBody::Initialize(capabilities)))?;
+        self.send_event(Event::Initialized)
+    }
+
+    /// Send a `telemetry` output event. By convention the `output` attribute carries the name of
+    /// the telemetry event and `data` carries its (structured) properties. Clients forward these
+    /// to telemetry instead of showing them to the user.
+    pub fn send_telemetry(&mut self, event_name: &str, data: Value) -> Result<(), ServerError> {
+        self.send_event(Event::Output(OutputEventBody {
+            category: Some(OutputEventCategory::Telemetry),
+            output: event_name.to_string(),
+            data: Some(data),
+            ..Default::default()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::client::{Client, ClientInbound};
+    use crate::errors::ValidationError;
+    use crate::loaded_sources::LoadedSources;
+    use crate::requests::{AttachOrLaunchArguments, RestartArguments};
+    use crate::responses::{
+        RunInTerminalResponse, SetInstructionBreakpointsResponse, ThreadsResponse,
+    };
+    use crate::reverse_requests::{ReverseCommand, RunInTerminalRequestArguments};
+    use crate::types::ColumnDescriptor;
+
+    fn frame(json: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", json.len(), json)
+    }
+
+    fn written_output(server: &Server<&mut Cursor<Vec<u8>>, Vec<u8>>) -> String {
+        let output = server.output.lock().unwrap();
+        String::from_utf8(output.output_buffer.get_ref().clone()).unwrap()
+    }
+
+    fn simulate_poll_request(input: &str) -> Request {
+        let mut server_in = Cursor::new(input.as_bytes().to_vec());
+        let server_out = Vec::new();
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(server_out));
+
+        server.poll_request().unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_server_init_request() {
+        let req = simulate_poll_request(
+            "Content-Length: 155\r\n\r\n{\"seq\": 152,\"type\": \"request\",\"command\": \"initialize\",\"arguments\": {\"adapterID\": \"0001e357-72c7-4f03-ae8f-c5b54bd8dabf\", \"clientName\": \"Some Cool Editor\"}}",
+        );
+
+        assert_eq!(req.seq, 152);
+        assert!(matches!(req.command, Command::Initialize { .. }));
+    }
+
+    #[test]
+    fn test_server_restart_request() {
+        let req = simulate_poll_request(
+            "Content-Length: 67\r\n\r\n{\"seq\": 152,\"type\": \"request\",\"command\": \"restart\",\"arguments\": {}}",
+        );
+
+        assert!(matches!(
+            req.command,
+            Command::Restart {
+                0: RestartArguments { arguments: None }
+            }
+        ));
+
+        // Restarting a launch request
+        let req = simulate_poll_request(
+            "Content-Length: 96\r\n\r\n{\"seq\": 152,\"type\": \"request\",\"command\": \"restart\",\"arguments\": {\"arguments\": {\"noDebug\":true}}}",
+        );
+        assert!(matches!(
+            req.command,
+            Command::Restart {
+                0: RestartArguments {
+                    arguments: Some(AttachOrLaunchArguments {
+                        no_debug: Some(_),
+                        ..
+                    })
+                }
+            }
+        ));
+
+        // Restarting a launch or attach request
+        let req = simulate_poll_request(
+            "Content-Length: 98\r\n\r\n{\"seq\": 152,\"type\": \"request\",\"command\": \"restart\",\"arguments\": {\"arguments\": {\"__restart\":true}}}",
+        );
+        assert!(matches!(
+            req.command,
+            Command::Restart {
+                0: RestartArguments {
+                    arguments: Some(AttachOrLaunchArguments {
+                        restart_data: Some(Value::Bool(true)),
+                        ..
+                    })
+                }
+            }
+        ));
+    }
+
+    #[test]
+    fn test_server_content_type_charset() {
+        let req = simulate_poll_request(
+            "Content-Length: 67\r\nContent-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\n{\"seq\": 152,\"type\": \"request\",\"command\": \"restart\",\"arguments\": {}}",
+        );
+        assert!(matches!(req.command, Command::Restart(_)));
+
+        let input = "Content-Length: 67\r\nContent-Type: application/vscode-jsonrpc; charset=utf-16\r\n\r\n{\"seq\": 152,\"type\": \"request\",\"command\": \"restart\",\"arguments\": {}}";
+        let mut server_in = Cursor::new(input.as_bytes().to_vec());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        let err = server.poll_request().unwrap_err();
+        assert!(matches!(
+            err,
+            ServerError::UnsupportedCharset { charset } if charset == "utf-16"
+        ));
+    }
+
+    #[test]
+    fn test_server_poll_validated() {
+        let input = frame(
+            "{\"seq\": 1,\"type\": \"request\",\"command\": \"setBreakpoints\",\"arguments\": {\"source\": {\"path\": \"notes.txt\"}}}",
+        ) + &frame(
+            "{\"seq\": 2,\"type\": \"request\",\"command\": \"setBreakpoints\",\"arguments\": {\"source\": {\"path\": \"main.lua\"}}}",
+        );
+        let mut server_in = Cursor::new(input.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        server.set_validator(Box::new(|request| match &request.command {
+            Command::SetBreakpoints(args)
+                if !args.source.path.as_deref().unwrap_or("").ends_with(".lua") =>
+            {
+                Err("breakpoints are only supported in Lua files".to_string())
+            }
+            _ => Ok(()),
+        }));
+
+        let req = server.poll_validated().unwrap().unwrap();
+        assert_eq!(req.seq, 2);
+
+        let output = written_output(&server);
+        assert!(output.contains("\"request_seq\":1"));
+        assert!(output.contains("\"success\":false"));
+        assert!(output.contains("breakpoints are only supported in Lua files"));
+
+        assert!(server.poll_validated().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_server_dispatch_catches_panics() {
+        let input = frame("{\"seq\": 3,\"type\": \"request\",\"command\": \"threads\"}")
+            + &frame("{\"seq\": 4,\"type\": \"request\",\"command\": \"threads\"}");
+        let mut server_in = Cursor::new(input.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .dispatch(req, |_| panic!("thread list is corrupted"))
+            .unwrap();
+
+        // The server is still usable after the panic
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .dispatch(req, |req| {
+                Some(req.success(ResponseBody::Threads(ThreadsResponse { threads: vec![] })))
+            })
+            .unwrap();
+
+        let output = written_output(&server);
+        assert!(output.contains("\"request_seq\":3,\"success\":false"));
+        assert!(output.contains(
+            r#""message":"internal error in debug adapter: thread list is corrupted","command":"threads""#
+        ));
+        assert!(output.contains("\"event\":\"output\""));
+        assert!(output.contains("\"request_seq\":4,\"success\":true"));
+    }
+
+    #[test]
+    fn test_server_send_telemetry() {
+        let mut server_in = Cursor::new(Vec::new());
+        let server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        server
+            .output
+            .lock()
+            .unwrap()
+            .send_telemetry(
+                "launch",
+                serde_json::json!({"runtime": "lua5.4", "durationMs": 120}),
+            )
+            .unwrap();
+
+        let output = written_output(&server);
+        let (_, body) = output.split_once("\r\n\r\n").unwrap();
+        let message: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(message["type"], "event");
+        assert_eq!(message["event"], "output");
+        assert_eq!(message["body"]["category"], "telemetry");
+        assert_eq!(message["body"]["output"], "launch");
+        assert_eq!(message["body"]["data"]["runtime"], "lua5.4");
+        assert_eq!(message["body"]["data"]["durationMs"], 120);
+    }
+
+    #[test]
+    fn test_server_complete_initialize() {
+        let input = frame(
+            "{\"seq\": 1,\"type\": \"request\",\"command\": \"initialize\",\"arguments\": {\"adapterID\": \"emmylua\"}}",
+        );
+        let mut server_in = Cursor::new(input.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        let req = server.poll_request().unwrap().unwrap();
+        let caps = Capabilities {
+            supports_configuration_done_request: Some(true),
+            ..Default::default()
+        };
+        server.complete_initialize(req, caps).unwrap();
+
+        let output = written_output(&server);
+        let response = output.find("\"type\":\"response\"").unwrap();
+        let initialized = output.find("\"event\":\"initialized\"").unwrap();
+        assert!(response < initialized);
+        assert!(output.contains("\"seq\":1,\"type\":\"response\",\"request_seq\":1"));
+        assert!(output.contains("\"supportsConfigurationDoneRequest\":true"));
+        assert!(output.contains("\"seq\":2,\"type\":\"event\""));
+    }
+
+    #[test]
+    fn test_server_large_body() {
+        // A ~10MB body, far larger than the default 8KiB read buffer, followed by another
+        // message to make sure the stream stays framed correctly.
+        let expression = "x".repeat(10 * 1024 * 1024);
+        let input = frame(&format!(
+            "{{\"seq\": 1,\"type\": \"request\",\"command\": \"evaluate\",\"arguments\": {{\"expression\": \"{expression}\"}}}}"
+        )) + &frame("{\"seq\": 2,\"type\": \"request\",\"command\": \"threads\"}");
+        let mut server_in = Cursor::new(input.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        let req = server.poll_request().unwrap().unwrap();
+        match req.command {
+            Command::Evaluate(args) => assert_eq!(args.expression.len(), expression.len()),
+            _ => panic!("Expected Command::Evaluate"),
+        }
+
+        let req = server.poll_request().unwrap().unwrap();
+        assert_eq!(req.seq, 2);
+    }
+
+    #[test]
+    fn test_stopped_with_hit_breakpoint_ids() {
+        let mut server_in = Cursor::new(Vec::new());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        let first = server.allocate_breakpoint_id().unwrap();
+        let second = server.allocate_breakpoint_id().unwrap();
+        assert_ne!(first, second);
+
+        server
+            .send_stopped(
+                StoppedEventBody::new(StoppedEventReason::Breakpoint)
+                    .with_thread_id(1)
+                    .with_hit_breakpoint_ids([second]),
+            )
+            .unwrap();
+        let output = written_output(&server);
+        assert!(output.contains(r#""event":"stopped""#));
+        assert!(output.contains(&format!(r#""hitBreakpointIds":[{second}]"#)));
+
+        let err = server
+            .send_stopped(
+                StoppedEventBody::new(StoppedEventReason::Breakpoint).with_hit_breakpoint_ids([99]),
+            )
+            .unwrap_err();
+        assert!(matches!(err, ServerError::UnknownBreakpointId { id: 99 }));
+        assert_eq!(written_output(&server), output);
+    }
+
+    #[test]
+    fn test_early_events_are_buffered() {
+        let init = frame(
+            r#"{"seq": 1,"type": "request","command": "initialize","arguments": {"adapterID": "test"}}"#,
+        );
+        let mut server_in = Cursor::new(init.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        server.set_buffer_early_events(true).unwrap();
+
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .send_event(Event::Output(OutputEventBody {
+                output: "early\n".to_string(),
+                ..Default::default()
+            }))
+            .unwrap();
+        assert_eq!(written_output(&server), "");
+
+        server
+            .complete_initialize(req, Capabilities::default())
+            .unwrap();
+        let output = written_output(&server);
+        let response = output.find(r#""command":"initialize""#).unwrap();
+        let early = output.find(r#""event":"output""#).unwrap();
+        let initialized = output.find(r#""event":"initialized""#).unwrap();
+        assert!(response < early);
+        assert!(early < initialized);
+    }
+
+    #[test]
+    fn test_early_events_flushed_by_failed_initialize() {
+        let init = frame(
+            r#"{"seq": 1,"type": "request","command": "initialize","arguments": {"adapterID": "test"}}"#,
+        );
+        let mut server_in = Cursor::new(init.clone().into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        server.set_buffer_early_events(true).unwrap();
+        let early = || {
+            Sendable::Event(Event::Output(OutputEventBody {
+                output: "early\n".to_string(),
+                ..Default::default()
+            }))
+        };
+
+        // An error response naming the command
+        let req = server.poll_request().unwrap().unwrap();
+        server.send(early()).unwrap();
+        server
+            .respond(Response::error_for(&req, "unsupported client"))
+            .unwrap();
+        let output = written_output(&server);
+        let response = output.find(r#""command":"initialize""#).unwrap();
+        assert!(response < output.find(r#""event":"output""#).unwrap());
+
+        // A plain error response, recognized by its request_seq
+        let mut server_in = Cursor::new(init.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        server.set_buffer_early_events(true).unwrap();
+        let req = server.poll_request().unwrap().unwrap();
+        server.send(early()).unwrap();
+        server.respond(req.error("unsupported client")).unwrap();
+        let output = written_output(&server);
+        let response = output.find(r#""request_seq":1"#).unwrap();
+        assert!(response < output.find(r#""event":"output""#).unwrap());
+    }
+
+    #[test]
+    fn test_negotiated_capabilities() {
+        let init = frame(
+            r#"{"seq": 1,"type": "request","command": "initialize","arguments": {"adapterID": "test","supportsVariableType": true,"linesStartAt1": false}}"#,
+        );
+        let mut server_in = Cursor::new(init.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        assert!(!server.negotiated().supports_variable_type());
+
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .complete_initialize(req, Capabilities::default().with_mutation_support())
+            .unwrap();
+
+        let negotiated = server.negotiated();
+        assert!(negotiated.supports_variable_type());
+        assert!(!negotiated.supports_memory_references());
+        assert!(!negotiated.lines_start_at1());
+        assert!(negotiated.columns_start_at1());
+        assert!(negotiated.adapter_supports(|caps| caps.supports_set_variable));
+        assert!(!negotiated.adapter_supports(|caps| caps.supports_log_points));
+    }
+
+    #[test]
+    fn test_dispatch_rejects_loaded_sources_without_capability() {
+        let input = frame(
+            r#"{"seq": 1,"type": "request","command": "initialize","arguments": {"adapterID": "test"}}"#,
+        ) + &frame(r#"{"seq": 2,"type": "request","command": "loadedSources"}"#)
+            + &frame(r#"{"seq": 3,"type": "request","command": "loadedSources"}"#);
+        let mut server_in = Cursor::new(input.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .complete_initialize(req, Capabilities::default())
+            .unwrap();
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .dispatch(req, |_| panic!("handler must not run"))
+            .unwrap();
+        let output = written_output(&server);
+        assert!(output.contains(r#""request_seq":2"#));
+        assert!(output.contains("supportsLoadedSourcesRequest"));
+
+        // With the capability advertised the request reaches the handler.
+        server.negotiated = Some(Negotiated {
+            adapter: Capabilities::default().with_supports_loaded_sources_request(true),
+            ..Default::default()
+        });
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .dispatch(req, |req| {
+                Some(req.success(ResponseBody::LoadedSources(LoadedSources::new().response())))
+            })
+            .unwrap();
+        assert!(written_output(&server).contains(r#""command":"loadedSources""#));
+    }
+
+    #[test]
+    fn test_capabilities_survive_restart() {
+        let init = frame(
+            r#"{"seq": 1,"type": "request","command": "initialize","arguments": {"adapterID": "test","supportsMemoryReferences": true}}"#,
+        );
+        let mut server_in = Cursor::new(init.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .complete_initialize(req, Capabilities::default().with_memory_support())
+            .unwrap();
+        let old_breakpoint = server.allocate_breakpoint_id().unwrap();
+
+        server.reset_session_keep_capabilities().unwrap();
+
+        let negotiated = server.negotiated();
+        assert!(negotiated.supports_memory_references());
+        assert!(negotiated.adapter_supports(|caps| caps.supports_read_memory_request));
+        let err = server
+            .send_stopped(
+                StoppedEventBody::new(StoppedEventReason::Breakpoint)
+                    .with_hit_breakpoint_ids([old_breakpoint]),
+            )
+            .unwrap_err();
+        assert!(matches!(err, ServerError::UnknownBreakpointId { .. }));
+        assert_ne!(server.allocate_breakpoint_id().unwrap(), old_breakpoint);
+    }
+
+    #[test]
+    fn test_dispatch_gates_completions() {
+        let completions = r#"{"seq": 2,"type": "request","command": "completions","arguments": {"text": "pla","column": 4}}"#;
+        let mut server_in = Cursor::new(frame(completions).repeat(2).into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        server.negotiated = Some(Negotiated::default());
+
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .dispatch(req, |_| panic!("handler must not run"))
+            .unwrap();
+        let output = written_output(&server);
+        assert!(output.contains(r#"(supportsCompletionsRequest)","command":"completions""#));
+
+        server.negotiated = Some(Negotiated {
+            adapter: Capabilities::default().with_supports_completions_request(true),
+            ..Default::default()
+        });
+        let req = server.poll_request().unwrap().unwrap();
+        let mut handled = false;
+        server
+            .dispatch(req, |req| {
+                handled = true;
+                Some(req.error("no completions"))
+            })
+            .unwrap();
+        assert!(handled);
+    }
+
+    #[test]
+    fn test_dispatch_rejects_second_initialize() {
+        let init = frame(
+            r#"{"seq": 1,"type": "request","command": "initialize","arguments": {"adapterID": "test"}}"#,
+        );
+        let mut server_in = Cursor::new(init.repeat(2).into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .complete_initialize(req, Capabilities::default().with_mutation_support())
+            .unwrap();
+        let before = written_output(&server);
+
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .dispatch(req, |_| panic!("handler must not run"))
+            .unwrap();
+        let output = written_output(&server)[before.len()..].to_string();
+        assert!(output.contains(r#""success":false"#));
+        assert!(output.contains(
+            r#""message":"the debug session has already been initialized","command":"initialize""#
+        ));
+        assert!(
+            server
+                .negotiated()
+                .adapter_supports(|caps| caps.supports_set_variable)
+        );
+    }
+
+    #[test]
+    fn test_dispatch_gates_modules() {
+        let modules = frame(r#"{"seq": 2,"type": "request","command": "modules","arguments": {}}"#);
+        let mut server_in = Cursor::new(modules.repeat(2).into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        // Columns without the modules request would be pointless, so they imply it
+        let adapter = Capabilities::default()
+            .with_modules_support(vec![ColumnDescriptor::new("loadAddress", "Load Address")]);
+        let val = serde_json::to_value(&adapter).unwrap();
+        assert_eq!(val["supportsModulesRequest"], true);
+        assert_eq!(
+            val["additionalModuleColumns"][0]["attributeName"],
+            "loadAddress"
+        );
+
+        server.negotiated = Some(Negotiated::default());
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .dispatch(req, |_| panic!("handler must not run"))
+            .unwrap();
+        assert!(written_output(&server).contains("supportsModulesRequest"));
+
+        server.negotiated = Some(Negotiated {
+            adapter,
+            ..Default::default()
+        });
+        let req = server.poll_request().unwrap().unwrap();
+        let mut handled = false;
+        server
+            .dispatch(req, |req| {
+                handled = true;
+                Some(req.error("no modules"))
+            })
+            .unwrap();
+        assert!(handled);
+    }
+
+    #[test]
+    fn test_dispatch_answers_unhandled_commands() {
+        let input = frame(
+            r#"{"seq": 1,"type": "request","command": "emmy/reloadScripts","arguments": {"all": true}}"#,
+        ) + &frame(r#"{"seq": 2,"type": "request","command": "threads"}"#);
+        let mut server_in = Cursor::new(input.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        let req = server.poll_request().unwrap().unwrap();
+        match &req.command {
+            Command::Custom(custom) => {
+                assert_eq!(custom.command, "emmy/reloadScripts");
+                assert_eq!(custom.arguments, Some(serde_json::json!({ "all": true })));
+            }
+            _ => panic!("Expected Command::Custom"),
+        }
+        server.dispatch(req, |_| None).unwrap();
+        let output = written_output(&server);
+        assert!(output.contains(r#""request_seq":1,"success":false"#));
+        assert!(
+            output.contains("The debug adapter does not handle the 'emmy/reloadScripts' request")
+        );
+
+        let req = server.poll_request().unwrap().unwrap();
+        server.dispatch(req, |_| None).unwrap();
+        assert!(
+            written_output(&server)
+                .contains("The debug adapter does not handle the 'threads' request")
+        );
+    }
+
+    #[test]
+    fn test_cancel_set_breakpoints_mid_flight() {
+        let input = frame(
+            r#"{"seq": 5,"type": "request","command": "setBreakpoints","arguments": {"source": {"path": "/game/main.lua"},"breakpoints": [{"line": 1},{"line": 2},{"line": 3},{"line": 4}]}}"#,
+        ) + &frame(
+            r#"{"seq": 6,"type": "request","command": "cancel","arguments": {"requestId": 5}}"#,
+        );
+        let mut server_in = Cursor::new(input.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        let req = server.poll_request().unwrap().unwrap();
+        let Command::SetBreakpoints(args) = &req.command else {
+            panic!("Expected Command::SetBreakpoints");
+        };
+        let lines: Vec<i64> = args
+            .breakpoints
+            .iter()
+            .flatten()
+            .map(|breakpoint| breakpoint.line)
+            .collect();
+        let request_seq = req.seq;
+
+        // Resolve the breakpoints slowly on another thread, stopping once cancelled
+        let cancellations = server.cancellations();
+        let (resolved_one, first_resolved) = std::sync::mpsc::channel();
+        let (cancel_seen, wait_for_cancel) = std::sync::mpsc::channel::<()>();
+        let resolver = std::thread::spawn(move || {
+            let mut breakpoints = Vec::new();
+            for line in lines {
+                if cancellations.is_cancelled(request_seq) {
+                    break;
+                }
+                breakpoints.push(crate::types::Breakpoint {
+                    verified: true,
+                    line: Some(line),
+                    ..Default::default()
+                });
+                if breakpoints.len() == 1 {
+                    resolved_one.send(()).unwrap();
+                    wait_for_cancel.recv().unwrap();
+                }
+            }
+            (cancellations.is_cancelled(request_seq), breakpoints)
+        });
+
+        first_resolved.recv().unwrap();
+        let cancel = server.poll_request().unwrap().unwrap();
+        assert!(matches!(cancel.command, Command::Cancel(_)));
+        assert!(server.cancellations().is_cancelled(request_seq));
+        cancel_seen.send(()).unwrap();
+
+        let (cancelled, breakpoints) = resolver.join().unwrap();
+        assert!(cancelled);
+        assert_eq!(breakpoints.len(), 1);
+
+        server.cancellations().finish(request_seq);
+        assert!(!server.cancellations().is_cancelled(request_seq));
+    }
+
+    #[test]
+    fn test_dispatch_gates_restart_frame() {
+        let restart_frame = frame(
+            r#"{"seq": 4,"type": "request","command": "restartFrame","arguments": {"frameId": 2}}"#,
+        );
+        let mut server_in = Cursor::new(restart_frame.repeat(2).into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        server.negotiated = Some(Negotiated::default());
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .dispatch(req, |_| panic!("handler must not run"))
+            .unwrap();
+        let output = written_output(&server);
+        assert!(output.contains(r#"(supportsRestartFrame)","command":"restartFrame""#));
+
+        server.negotiated = Some(Negotiated {
+            adapter: Capabilities::default().with_supports_restart_frame(true),
+            ..Default::default()
+        });
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .dispatch(req, |req| Some(req.ack().unwrap()))
+            .unwrap();
+        assert!(written_output(&server).contains(r#""command":"restartFrame""#));
+    }
+
+    #[test]
+    fn test_server_trailing_newline_after_body() {
+        let threads = r#"{"seq": 1,"type": "request","command": "threads"}"#;
+        let counted = format!("{threads}\n");
+        // Newline counted by Content-Length, then a newline the length does not include
+        let input = frame(&counted) + &frame(threads) + "\n" + &frame(threads);
+        let mut server_in = Cursor::new(input.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        let warnings = Arc::new(Mutex::new(Vec::new()));
+        let sink = warnings.clone();
+        server.set_warning_handler(Box::new(move |warning| {
+            sink.lock().unwrap().push(warning.to_string());
+        }));
+
+        for _ in 0..3 {
+            let req = server.poll_request().unwrap().unwrap();
+            assert!(matches!(req.command, Command::Threads));
+        }
+        assert!(server.poll_request().unwrap().is_none());
+        assert_eq!(warnings.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_poll_message_reverse_request_response() {
+        let reply = r#"{"seq": 4, "type": "response", "request_seq": 2, "success": true, "command": "runInTerminal", "body": {"processId": 4711}}"#;
+        let threads = r#"{"seq": 5, "type": "request", "command": "threads"}"#;
+        let input = frame(reply) + &frame(threads) + &frame(reply);
+        let mut server_in = Cursor::new(input.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        let Some(IncomingMessage::Response(response)) = server.poll_message().unwrap() else {
+            panic!("expected a response");
+        };
+        assert_eq!(response.request_seq, 2);
+        assert!(matches!(
+            response.body,
+            Some(ResponseBody::RunInTerminal(RunInTerminalResponse {
+                process_id: Some(4711),
+                shell_process_id: None,
+            }))
+        ));
+        assert!(matches!(
+            server.poll_message().unwrap(),
+            Some(IncomingMessage::Request(Request {
+                command: Command::Threads,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            server.poll_request(),
+            Err(ServerError::UnexpectedMessage { .. })
+        ));
+    }
+
+    #[test]
+    fn test_server_skip_leading_blanks() {
+        let threads = r#"{"seq": 1, "type": "request", "command": "threads"}"#;
+        let input = format!("\r\n\r\n{}", frame(threads));
+
+        let mut server_in = Cursor::new(input.clone().into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        server.skip_leading_blanks(true);
+        let req = server.poll_request().unwrap().unwrap();
+        assert!(matches!(req.command, Command::Threads));
+
+        let mut server_in = Cursor::new(input.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        server.skip_leading_blanks(false);
+        assert!(matches!(
+            server.poll_request(),
+            Err(ServerError::ProtocolError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_dispatch_gates_terminate_threads() {
+        let terminate_threads = frame(
+            r#"{"seq": 6,"type": "request","command": "terminateThreads","arguments": {"threadIds": [1, 2]}}"#,
+        );
+        let mut server_in = Cursor::new(terminate_threads.repeat(2).into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        server.negotiated = Some(Negotiated::default());
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .dispatch(req, |_| panic!("handler must not run"))
+            .unwrap();
+        assert!(written_output(&server).contains("supportsTerminateThreadsRequest"));
+
+        server.negotiated = Some(Negotiated {
+            adapter: Capabilities::default().with_supports_terminate_threads_request(true),
+            ..Default::default()
+        });
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .dispatch(req, |req| Some(req.ack().unwrap()))
+            .unwrap();
+        assert!(written_output(&server).contains(r#""success":true"#));
+    }
+
+    #[test]
+    fn test_set_variable_invalidates_frame_variables() {
+        let set_variable = frame(
+            r#"{"seq": 9,"type": "request","command": "setVariable","arguments": {"variablesReference": 3,"name": "hp","value": "100"}}"#,
+        );
+        let mut server_in = Cursor::new(set_variable.repeat(2).into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        // Without supportsInvalidatedEvent the client would not act on the event
+        server.negotiated = Some(Negotiated::default());
+        server.poll_request().unwrap().unwrap();
+        server.send_variables_invalidated(1, Some(4)).unwrap();
+        assert!(written_output(&server).is_empty());
+
+        server.negotiated = Some(Negotiated {
+            client: InitializeArguments {
+                supports_invalidated_event: Some(true),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let req = server.poll_request().unwrap().unwrap();
+        assert!(matches!(req.command, Command::SetVariable(_)));
+        server.send_variables_invalidated(1, Some(4)).unwrap();
+        let output = written_output(&server);
+        assert!(output.contains(r#""event":"invalidated""#));
+        assert!(output.contains(r#""areas":["variables"],"threadId":1,"stackFrameId":4"#));
+    }
+
+    #[test]
+    fn test_dispatch_gates_set_instruction_breakpoints() {
+        let set_instruction_breakpoints = frame(
+            r#"{"seq": 3,"type": "request","command": "setInstructionBreakpoints","arguments": {"breakpoints": [{"instructionReference": "0x1000"}]}}"#,
+        );
+        let mut server_in = Cursor::new(set_instruction_breakpoints.repeat(2).into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        server.negotiated = Some(Negotiated::default());
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .dispatch(req, |_| panic!("handler must not run"))
+            .unwrap();
+        assert!(written_output(&server).contains("supportsInstructionBreakpoints"));
+
+        server.negotiated = Some(Negotiated {
+            adapter: Capabilities::default().with_supports_instruction_breakpoints(true),
+            ..Default::default()
+        });
+        let req = server.poll_request().unwrap().unwrap();
+        server
+            .dispatch(req, |req| {
+                Some(req.success(ResponseBody::SetInstructionBreakpoints(
+                    SetInstructionBreakpointsResponse {
+                        breakpoints: vec![],
+                    },
+                )))
+            })
+            .unwrap();
+        assert!(written_output(&server).contains(r#""success":true"#));
+    }
+
+    #[test]
+    fn test_server_unknown_headers() {
+        let threads = r#"{"seq": 1, "type": "request", "command": "threads"}"#;
+        let input = format!(
+            "X-Trace-Id: 42\r\nContent-Length: {}\r\n\r\n{}",
+            threads.len(),
+            threads
+        );
+
+        let mut server_in = Cursor::new(input.clone().into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        let req = server.poll_request().unwrap().unwrap();
+        assert!(matches!(req.command, Command::Threads));
+
+        let mut server_in = Cursor::new(input.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        server.strict_headers(true);
+        assert!(matches!(
+            server.poll_request(),
+            Err(ServerError::UnknownHeader { header }) if header == "X-Trace-Id"
+        ));
+
+        let input = format!("X-Trace-Id: 42\r\n\r\n{threads}");
+        let mut server_in = Cursor::new(input.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        assert!(matches!(
+            server.poll_request(),
+            Err(ServerError::ProtocolError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_poll_request_lossy_skips_malformed_message() {
+        let truncated = r#"{"seq": 1, "type": "request", "comm"#;
+        let threads = r#"{"seq": 2, "type": "request", "command": "threads"}"#;
+        let input = frame(truncated) + &frame(threads);
+        let mut server_in = Cursor::new(input.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        match server.poll_request_lossy().unwrap() {
+            Some(Err(ServerError::ParseError { content, .. })) => assert_eq!(content, truncated),
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+        let req = server.poll_request_lossy().unwrap().unwrap().unwrap();
+        assert_eq!(req.seq, 2);
+        assert!(server.poll_request_lossy().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_send_exception_stop() {
+        let mut server_in = Cursor::new(Vec::new());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        server
+            .send_stopped(
+                StoppedEventBody::exception("NullReferenceError", "attempt to index a nil value")
+                    .with_thread_id(1),
+            )
+            .unwrap();
+        let output = written_output(&server);
+        assert!(output.contains(r#""reason":"exception""#));
+        assert!(output.contains(r#""description":"attempt to index a nil value""#));
+        assert!(output.contains(r#""text":"NullReferenceError""#));
+
+        let step = StoppedEventBody::new(StoppedEventReason::Step).with_text("NullReferenceError");
+        assert!(matches!(
+            server.send_stopped(step),
+            Err(ServerError::InvalidEvent { .. })
+        ));
+    }
+
+    #[test]
+    fn test_dispatch_gates_data_breakpoints() {
+        let info = frame(
+            r#"{"seq": 2,"type": "request","command": "dataBreakpointInfo","arguments": {"name": "hp","variablesReference": 3}}"#,
+        );
+        let info_bytes = frame(
+            r#"{"seq": 3,"type": "request","command": "dataBreakpointInfo","arguments": {"name": "0x1000","asAddress": true,"bytes": 8}}"#,
+        );
+        let set = frame(
+            r#"{"seq": 4,"type": "request","command": "setDataBreakpoints","arguments": {"breakpoints": []}}"#,
+        );
+        let info_by_name = frame(
+            r#"{"seq": 5,"type": "request","command": "dataBreakpointInfo","arguments": {"name": "hp","asAddress": false}}"#,
+        );
+        let input = info.clone() + &set + &info_bytes + &info + &set + &info_bytes + &info_by_name;
+        let mut server_in = Cursor::new(input.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+
+        server.negotiated = Some(Negotiated::default());
+        for _ in 0..3 {
+            let req = server.poll_request().unwrap().unwrap();
+            server
+                .dispatch(req, |_| panic!("handler must not run"))
+                .unwrap();
+        }
+        assert_eq!(
+            written_output(&server)
+                .matches("(supportsDataBreakpoints)")
+                .count(),
+            3
+        );
+
+        server.negotiated = Some(Negotiated {
+            adapter: Capabilities::default().with_supports_data_breakpoints(true),
+            ..Default::default()
+        });
+        let mut handled = Vec::new();
+        for _ in 0..4 {
+            let req = server.poll_request().unwrap().unwrap();
+            server
+                .dispatch(req, |req| {
+                    handled.push(req.seq);
+                    Some(req.error("not implemented"))
+                })
+                .unwrap();
+        }
+        // The address form additionally needs supportsDataBreakpointBytes, `asAddress: false`
+        // does not
+        assert_eq!(handled, [2, 4, 5]);
+        assert!(written_output(&server).contains("supportsDataBreakpointBytes"));
+    }
+
+    #[test]
+    fn test_server_message_too_large() {
+        let input = "Content-Length: 9999999999\r\n\r\n{}";
+        let mut server_in = Cursor::new(input.as_bytes().to_vec());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        assert!(matches!(
+            server.poll_request(),
+            Err(ServerError::MessageTooLarge {
+                length: 9_999_999_999,
+                limit: DEFAULT_MAX_MESSAGE_SIZE,
+            })
+        ));
+
+        let threads = r#"{"seq": 1, "type": "request", "command": "threads"}"#;
+        let mut server_in = Cursor::new(frame(threads).into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        server.max_message_size(16);
+        assert!(matches!(
+            server.poll_request(),
+            Err(ServerError::MessageTooLarge { limit: 16, .. })
+        ));
+    }
+
+    #[test]
+    fn test_cancel_progress() {
+        let input = frame(
+            r#"{"seq": 3,"type": "request","command": "cancel","arguments": {"progressId": "indexing"}}"#,
+        );
+        let mut server_in = Cursor::new(input.into_bytes());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        let cancellations = server.cancellations();
+
+        assert!(!cancellations.is_progress_cancelled("indexing"));
+        server.poll_request().unwrap().unwrap();
+        assert!(cancellations.is_progress_cancelled("indexing"));
+        assert!(!cancellations.is_cancelled(3));
+
+        cancellations.finish_progress("indexing");
+        assert!(!cancellations.is_progress_cancelled("indexing"));
+    }
+
+    #[test]
+    fn test_send_reuses_serialization_buffer() {
+        let mut server_in = Cursor::new(Vec::new());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        let event = || Event::output(OutputEventCategory::Stdout, "x".repeat(4096));
+
+        server.send_event(event()).unwrap();
+        let capacity = server.output.lock().unwrap().encoder.scratch.capacity();
+        assert!(capacity >= 4096);
+        for _ in 0..100 {
+            server.send_event(event()).unwrap();
+        }
+        assert_eq!(
+            server.output.lock().unwrap().encoder.scratch.capacity(),
+            capacity
+        );
+
+        // The wire format is unchanged
+        let output = written_output(&server);
+        let first = frame(
+            &serde_json::to_string(&BaseMessage {
+                seq: 1,
+                message: Sendable::Event(event()),
+            })
+            .unwrap(),
+        );
+        assert!(output.starts_with(&first));
+        assert_eq!(output.matches("Content-Length: ").count(), 101);
+
+        // A huge message does not keep its buffer around
+        let huge = "x".repeat(2 * RETAINED_SCRATCH_CAPACITY);
+        server
+            .send_event(Event::output(OutputEventCategory::Stdout, huge))
+            .unwrap();
+        assert!(
+            server.output.lock().unwrap().encoder.scratch.capacity() <= RETAINED_SCRATCH_CAPACITY
+        );
+    }
+
+    #[test]
+    fn test_send_reverse_request_returns_seq() {
+        let mut server_in = Cursor::new(Vec::new());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        server.send_event(Event::Initialized).unwrap();
+        let seq = server
+            .send_reverse_request(ReverseRequest {
+                seq: 0,
+                command: ReverseCommand::RunInTerminal(RunInTerminalRequestArguments::new(
+                    "/tmp",
+                    vec!["lua".to_string()],
+                )),
+            })
+            .unwrap();
+        assert_eq!(seq, 2);
+
+        // The client sees a plain request carrying the returned seq
+        let output = written_output(&server);
+        assert_eq!(output.matches(r#""seq""#).count(), 2);
+        let mut client = Client::new(
+            BufReader::new(Cursor::new(output.into_bytes())),
+            BufWriter::new(Vec::new()),
+        );
+        client.poll_event_or_response().unwrap();
+        let Some(ClientInbound::ReverseRequest(request)) = client.poll_event_or_response().unwrap()
+        else {
+            panic!("expected a reverse request");
+        };
+        assert_eq!(request.seq, seq);
+    }
+
+    #[test]
+    fn test_validate_messages() {
+        let mut server_in = Cursor::new(Vec::new());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        let unnamed_stop = || Event::stopped(StoppedEventReason::Pause, None);
+
+        server.send_event(unnamed_stop()).unwrap();
+        server.set_validate_messages(true).unwrap();
+        assert!(matches!(
+            server.send_event(unnamed_stop()),
+            Err(ServerError::InvalidMessage(
+                ValidationError::StoppedWithoutThread
+            ))
+        ));
+        server
+            .send_event(Event::stopped(StoppedEventReason::Pause, Some(1)))
+            .unwrap();
+        // Structured error responses carry nothing but `body.error`
+        let request = Request {
+            seq: 1,
+            command: Command::Threads,
+        };
+        server.dispatch(request, |_| None).unwrap();
+        let output = written_output(&server);
+        assert_eq!(output.matches("Content-Length: ").count(), 3);
+        assert!(output.contains(r#""threadId":1"#));
+    }
+
+    /// Hands out one byte per read, stalling once when it reaches `stall_at`.
+    struct Trickle {
+        data: Vec<u8>,
+        position: usize,
+        stall_at: usize,
+        stalled: bool,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.position == self.stall_at && !self.stalled {
+                self.stalled = true;
+                return Err(std::io::ErrorKind::WouldBlock.into());
+            }
+            let Some(&byte) = self.data.get(self.position) else {
+                return Ok(0);
+            };
+            buf[0] = byte;
+            self.position += 1;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_server_reads_byte_by_byte() {
+        let input = frame(r#"{"seq": 1, "type": "request", "command": "threads"}"#)
+            + &frame(r#"{"seq": 2, "type": "request", "command": "configurationDone"}"#);
+        // Stall in the middle of the first header line, then in the middle of the second body
+        for stall_at in [5, input.len() - 10] {
+            let trickle = Trickle {
+                data: input.clone().into_bytes(),
+                position: 0,
+                stall_at,
+                stalled: false,
+            };
+            let mut server = Server::new(BufReader::new(trickle), BufWriter::new(Vec::new()));
+            let mut requests = Vec::new();
+            let mut stalls = 0;
+            loop {
+                match server.poll_request() {
+                    Ok(Some(request)) => requests.push(request.seq),
+                    Ok(None) => break,
+                    Err(ServerError::IoError(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        stalls += 1;
+                    }
+                    Err(e) => panic!("unexpected error: {e}"),
+                }
+            }
+            // The stall is reported, and polling again picks up where it stopped
+            assert_eq!(stalls, 1);
+            assert_eq!(requests, [1, 2]);
+        }
+
+        let trickle = Trickle {
+            data: input.into_bytes(),
+            position: 0,
+            stall_at: usize::MAX,
+            stalled: false,
+        };
+        let mut server = Server::new(BufReader::new(trickle), BufWriter::new(Vec::new()));
+
+        let request = server.poll_request().unwrap().unwrap();
+        assert!(matches!(request.command, Command::Threads));
+        let request = server.poll_request().unwrap().unwrap();
+        assert!(matches!(request.command, Command::ConfigurationDone));
+        assert!(server.poll_request().unwrap().is_none());
+
+        // A connection closed in the middle of a header is not a clean EOF
+        let mut server_in = Cursor::new(b"Content-Len".to_vec());
+        let mut server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        assert!(matches!(
+            server.poll_request(),
+            Err(ServerError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof
+        ));
+    }
+
+    #[test]
+    fn test_stop_barrier() {
+        let mut server_in = Cursor::new(Vec::new());
+        let server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        let barrier = server.stop_barrier();
+
+        barrier
+            .stop(StoppedEventBody::new(StoppedEventReason::Step).with_thread_id(1))
+            .unwrap();
+        barrier
+            .stop(StoppedEventBody::new(StoppedEventReason::Breakpoint).with_thread_id(1))
+            .unwrap();
+        assert!(written_output(&server).is_empty());
+
+        let preparer = barrier.clone();
+        std::thread::scope(|scope| {
+            scope.spawn(|| assert!(preparer.stack_ready().unwrap()));
+        });
+        let output = written_output(&server);
+        assert_eq!(output.matches(r#""event":"stopped""#).count(), 1);
+        assert!(output.contains(r#""reason":"breakpoint""#));
+
+        assert!(!barrier.stack_ready().unwrap());
+    }
+
+    #[test]
+    fn test_stop_barrier_sends_past_invalid_stop() {
+        let mut server_in = Cursor::new(Vec::new());
+        let server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        let barrier = server.stop_barrier();
+
+        barrier
+            .stop(
+                StoppedEventBody::new(StoppedEventReason::Breakpoint)
+                    .with_thread_id(1)
+                    .with_hit_breakpoint_ids([99]),
+            )
+            .unwrap();
+        barrier
+            .stop(StoppedEventBody::new(StoppedEventReason::Pause).with_thread_id(2))
+            .unwrap();
+        let err = barrier.stack_ready().unwrap_err();
+        assert!(matches!(err, ServerError::UnknownBreakpointId { id: 99 }));
+
+        let output = written_output(&server);
+        assert_eq!(output.matches(r#""event":"stopped""#).count(), 1);
+        assert!(output.contains(r#""reason":"pause""#));
+        assert!(!barrier.stack_ready().unwrap());
+    }
+
+    #[test]
+    fn test_stop_barrier_keeps_other_threads() {
+        let mut server_in = Cursor::new(Vec::new());
+        let server = Server::new(BufReader::new(&mut server_in), BufWriter::new(Vec::new()));
+        let barrier = server.stop_barrier();
+
+        barrier
+            .stop(StoppedEventBody::new(StoppedEventReason::Breakpoint).with_thread_id(1))
+            .unwrap();
+        barrier
+            .stop(StoppedEventBody::new(StoppedEventReason::Pause).with_thread_id(2))
+            .unwrap();
+        barrier
+            .stop(StoppedEventBody::new(StoppedEventReason::Step).with_thread_id(1))
+            .unwrap();
+        assert!(barrier.stack_ready().unwrap());
+
+        let output = written_output(&server);
+        assert_eq!(output.matches(r#""event":"stopped""#).count(), 2);
+        let first = output.find(r#""reason":"step""#).unwrap();
+        let second = output.find(r#""reason":"pause""#).unwrap();
+        assert!(first < second);
+        assert!(!output.contains(r#""reason":"breakpoint""#));
     }
 }
@@ -0,0 +1,145 @@
+use std::time::{Duration, Instant};
+
+use crate::requests::DisconnectArguments;
+
+/// What the adapter should do about the debuggee when it receives a `disconnect` request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectAction {
+    /// The debuggee is already gone (or the adapter never started one), nothing to do.
+    Done,
+    /// Leave the debuggee running and detach from it.
+    Detach,
+    /// A `terminate` request asked the debuggee to shut down and the grace period is not over
+    /// yet. Give it at most `remaining` more time, then kill it.
+    Wait { remaining: Duration },
+    /// Kill the debuggee right away.
+    ForceKill,
+}
+
+/// Tracks the `terminate` → `disconnect` shutdown sequence.
+///
+/// Clients first send `terminate` to give the debuggee a chance to shut down gracefully, and
+/// `disconnect` later. Call [`ShutdownCoordinator::on_terminate`] when the `terminate` request
+/// arrives and [`ShutdownCoordinator::on_debuggee_exited`] when the debuggee ends;
+/// [`ShutdownCoordinator::disconnect_action`] then tells whether the `disconnect` has to
+/// force-kill it.
+#[derive(Debug, Clone)]
+pub struct ShutdownCoordinator {
+    grace_period: Duration,
+    launched: bool,
+    terminate_requested_at: Option<Instant>,
+    exited: bool,
+}
+
+impl ShutdownCoordinator {
+    /// Create a coordinator for a debuggee that was launched (`launched` is true) or attached
+    /// to, which gets `grace_period` to exit after a `terminate` request.
+    pub fn new(grace_period: Duration, launched: bool) -> Self {
+        Self {
+            grace_period,
+            launched,
+            terminate_requested_at: None,
+            exited: false,
+        }
+    }
+
+    /// Record that a `terminate` request was received, starting the grace period.
+    pub fn on_terminate(&mut self) {
+        self.on_terminate_at(Instant::now());
+    }
+
+    /// Like [`ShutdownCoordinator::on_terminate`], with the time the request was received.
+    /// Repeated `terminate` requests do not restart the grace period.
+    pub fn on_terminate_at(&mut self, now: Instant) {
+        self.terminate_requested_at.get_or_insert(now);
+    }
+
+    /// Record that the debuggee has exited.
+    pub fn on_debuggee_exited(&mut self) {
+        self.exited = true;
+    }
+
+    /// Whether a `terminate` request was received.
+    pub fn terminate_requested(&self) -> bool {
+        self.terminate_requested_at.is_some()
+    }
+
+    /// What to do about the debuggee for the `disconnect` request with `args`.
+    pub fn disconnect_action(&self, args: &DisconnectArguments) -> DisconnectAction {
+        self.disconnect_action_at(args, Instant::now())
+    }
+
+    /// Like [`ShutdownCoordinator::disconnect_action`], at the time `now`.
+    pub fn disconnect_action_at(
+        &self,
+        args: &DisconnectArguments,
+        now: Instant,
+    ) -> DisconnectAction {
+        if self.exited {
+            return DisconnectAction::Done;
+        }
+        if let Some(requested_at) = self.terminate_requested_at {
+            let elapsed = now.saturating_duration_since(requested_at);
+            return match self.grace_period.checked_sub(elapsed) {
+                Some(remaining) if !remaining.is_zero() => DisconnectAction::Wait { remaining },
+                _ => DisconnectAction::ForceKill,
+            };
+        }
+        // Without an explicit choice, launched debuggees are terminated and attached ones left
+        // running
+        if args.terminate_debuggee.unwrap_or(self.launched) {
+            DisconnectAction::ForceKill
+        } else {
+            DisconnectAction::Detach
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graceful_then_force() {
+        let args = DisconnectArguments::default();
+        let mut shutdown = ShutdownCoordinator::new(Duration::from_secs(5), true);
+        let start = Instant::now();
+        shutdown.on_terminate_at(start);
+        shutdown.on_terminate_at(start + Duration::from_secs(2));
+        assert!(shutdown.terminate_requested());
+
+        assert_eq!(
+            shutdown.disconnect_action_at(&args, start + Duration::from_secs(1)),
+            DisconnectAction::Wait {
+                remaining: Duration::from_secs(4)
+            }
+        );
+        assert_eq!(
+            shutdown.disconnect_action_at(&args, start + Duration::from_secs(6)),
+            DisconnectAction::ForceKill
+        );
+
+        shutdown.on_debuggee_exited();
+        assert_eq!(
+            shutdown.disconnect_action_at(&args, start + Duration::from_secs(6)),
+            DisconnectAction::Done
+        );
+    }
+
+    #[test]
+    fn test_disconnect_without_terminate() {
+        let attached = ShutdownCoordinator::new(Duration::from_secs(5), false);
+        assert_eq!(
+            attached.disconnect_action(&DisconnectArguments::default()),
+            DisconnectAction::Detach
+        );
+        let args = DisconnectArguments {
+            terminate_debuggee: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            attached.disconnect_action(&args),
+            DisconnectAction::ForceKill
+        );
+    }
+}
@@ -1,11 +1,17 @@
-use std::collections::HashMap;
+use alloc::{collections::BTreeMap, format, string::String, vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::errors::ValidationError;
+#[cfg(feature = "std")]
+use crate::requests::PathFormat;
+use crate::requests::{GotoArguments, InitializeArguments};
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum ColumnDescriptorType {
     String,
     Number,
@@ -32,8 +38,25 @@ pub struct ColumnDescriptor {
     pub width: Option<i64>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+impl ColumnDescriptor {
+    /// A column in the modules view showing the module attribute `attribute_name` under the
+    /// header `label`.
+    pub fn new(attribute_name: impl Into<String>, label: impl Into<String>) -> Self {
+        Self {
+            attribute_name: attribute_name.into(),
+            label: label.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the datatype of the values in this column.
+    pub fn with_type(mut self, column_type: ColumnDescriptorType) -> Self {
+        self.column_descriptor_type = Some(column_type);
+        self
+    }
+}
 
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub enum ChecksumAlgorithm {
     MD5,
     SHA1,
@@ -44,7 +67,6 @@ pub enum ChecksumAlgorithm {
 
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct Capabilities {
     /// The debug adapter supports the `configurationDone` request.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -147,6 +169,10 @@ pub struct Capabilities {
     /// The debug adapter supports data breakpoints.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub supports_data_breakpoints: Option<bool>,
+    /// The debug adapter supports the `asAddress` and `bytes` fields in the
+    /// `dataBreakpointInfo` request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supports_data_breakpoint_bytes: Option<bool>,
     /// The debug adapter supports the `readMemory` request.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub supports_read_memory_request: Option<bool>,
@@ -185,6 +211,271 @@ pub struct Capabilities {
     pub supports_single_thread_execution_requests: Option<bool>,
 }
 
+impl Capabilities {
+    /// The capabilities expected from a debugger for an interpreted language: configuration
+    /// done, function, conditional and hit count breakpoints, logpoints, evaluating for hovers,
+    /// setting variables and the `terminate` request. Adjust them with the `with_*` setters.
+    pub fn basic_interpreter() -> Self {
+        Capabilities {
+            supports_configuration_done_request: Some(true),
+            supports_function_breakpoints: Some(true),
+            supports_conditional_breakpoints: Some(true),
+            supports_hit_conditional_breakpoints: Some(true),
+            supports_log_points: Some(true),
+            supports_evaluate_for_hovers: Some(true),
+            supports_set_variable: Some(true),
+            supports_terminate_request: Some(true),
+            ..Default::default()
+        }
+    }
+
+    /// The capabilities of [`Capabilities::basic_interpreter`] plus those expected from a native
+    /// debugger: reading and writing memory, disassembly, instruction and data breakpoints,
+    /// stepping granularities and the `modules` request.
+    pub fn native_debugger() -> Self {
+        Capabilities {
+            supports_read_memory_request: Some(true),
+            supports_write_memory_request: Some(true),
+            supports_disassemble_request: Some(true),
+            supports_instruction_breakpoints: Some(true),
+            supports_data_breakpoints: Some(true),
+            supports_stepping_granularity: Some(true),
+            supports_modules_request: Some(true),
+            ..Self::basic_interpreter()
+        }
+    }
+
+    /// Set the `supportsSetVariable` capability.
+    pub fn with_supports_set_variable(mut self, value: bool) -> Self {
+        self.supports_set_variable = Some(value);
+        self
+    }
+
+    /// Set the `supportsSetExpression` capability.
+    pub fn with_supports_set_expression(mut self, value: bool) -> Self {
+        self.supports_set_expression = Some(value);
+        self
+    }
+
+    /// Set the `supportsReadMemoryRequest` capability.
+    pub fn with_supports_read_memory_request(mut self, value: bool) -> Self {
+        self.supports_read_memory_request = Some(value);
+        self
+    }
+
+    /// Set the `supportsWriteMemoryRequest` capability.
+    pub fn with_supports_write_memory_request(mut self, value: bool) -> Self {
+        self.supports_write_memory_request = Some(value);
+        self
+    }
+
+    /// Set the `supportsDisassembleRequest` capability.
+    pub fn with_supports_disassemble_request(mut self, value: bool) -> Self {
+        self.supports_disassemble_request = Some(value);
+        self
+    }
+
+    /// Advertise the `readMemory`, `writeMemory` and `disassemble` requests.
+    ///
+    /// Note that `supportsMemoryReferences` is a *client* capability (see
+    /// [`InitializeArguments`](crate::requests::InitializeArguments)): clients only show the
+    /// memory and disassembly views if they support it as well.
+    pub fn with_memory_support(self) -> Self {
+        self.with_supports_read_memory_request(true)
+            .with_supports_write_memory_request(true)
+            .with_supports_disassemble_request(true)
+    }
+
+    /// Set the `supportsFunctionBreakpoints` capability.
+    pub fn with_supports_function_breakpoints(mut self, value: bool) -> Self {
+        self.supports_function_breakpoints = Some(value);
+        self
+    }
+
+    /// Set the `supportsConditionalBreakpoints` capability.
+    pub fn with_supports_conditional_breakpoints(mut self, value: bool) -> Self {
+        self.supports_conditional_breakpoints = Some(value);
+        self
+    }
+
+    /// Set the `supportsHitConditionalBreakpoints` capability.
+    pub fn with_supports_hit_conditional_breakpoints(mut self, value: bool) -> Self {
+        self.supports_hit_conditional_breakpoints = Some(value);
+        self
+    }
+
+    /// Set the `supportsLogPoints` capability.
+    pub fn with_supports_log_points(mut self, value: bool) -> Self {
+        self.supports_log_points = Some(value);
+        self
+    }
+
+    /// Advertise conditional breakpoints, hit conditions and logpoints. Clients only offer the
+    /// matching breakpoint editing UI when these are set.
+    pub fn with_advanced_breakpoints(self) -> Self {
+        self.with_supports_conditional_breakpoints(true)
+            .with_supports_hit_conditional_breakpoints(true)
+            .with_supports_log_points(true)
+    }
+
+    /// Set the `supportsBreakpointLocationsRequest` capability.
+    pub fn with_supports_breakpoint_locations_request(mut self, value: bool) -> Self {
+        self.supports_breakpoint_locations_request = Some(value);
+        self
+    }
+
+    /// Set the `supportsGotoTargetsRequest` capability.
+    ///
+    /// Clients only offer "Jump to Cursor" (a `gotoTargets` request followed by `goto`) when
+    /// this is set, see [`GotoTarget::goto_arguments`].
+    pub fn with_supports_goto_targets_request(mut self, value: bool) -> Self {
+        self.supports_goto_targets_request = Some(value);
+        self
+    }
+
+    /// Set the `supportsEvaluateForHovers` capability. Clients only send `evaluate` requests
+    /// with the `hover` context, used for showing values when hovering over source, if it is set.
+    pub fn with_supports_evaluate_for_hovers(mut self, value: bool) -> Self {
+        self.supports_evaluate_for_hovers = Some(value);
+        self
+    }
+
+    /// Set the `supportsLoadedSourcesRequest` capability. Clients only show a loaded scripts
+    /// view (see [`LoadedSources`](crate::loaded_sources::LoadedSources)) if it is set.
+    pub fn with_supports_loaded_sources_request(mut self, value: bool) -> Self {
+        self.supports_loaded_sources_request = Some(value);
+        self
+    }
+
+    /// Set the `supportsCompletionsRequest` capability. Clients only ask for completions in the
+    /// debug console if it is set.
+    pub fn with_supports_completions_request(mut self, value: bool) -> Self {
+        self.supports_completions_request = Some(value);
+        self
+    }
+
+    /// Set the `supportsSteppingGranularity` capability. Clients only send a `granularity` with
+    /// stepping requests (e.g. for instruction stepping in the disassembly view) if it is set.
+    pub fn with_supports_stepping_granularity(mut self, value: bool) -> Self {
+        self.supports_stepping_granularity = Some(value);
+        self
+    }
+
+    /// The granularity a stepping request should be carried out with: the `requested` one if
+    /// `supportsSteppingGranularity` was advertised, [`SteppingGranularity::Statement`]
+    /// otherwise.
+    pub fn stepping_granularity(
+        &self,
+        requested: Option<SteppingGranularity>,
+    ) -> SteppingGranularity {
+        match requested {
+            Some(granularity) if self.supports_stepping_granularity == Some(true) => granularity,
+            _ => SteppingGranularity::default(),
+        }
+    }
+
+    /// Set the `supportsModulesRequest` capability.
+    pub fn with_supports_modules_request(mut self, value: bool) -> Self {
+        self.supports_modules_request = Some(value);
+        self
+    }
+
+    /// Advertise the `modules` request, with `columns` describing additional module
+    /// attributes for the client's modules view. The columns are only used by clients if the
+    /// `modules` request is supported, so this sets both.
+    pub fn with_modules_support(mut self, columns: Vec<ColumnDescriptor>) -> Self {
+        self.additional_module_columns = (!columns.is_empty()).then_some(columns);
+        self.with_supports_modules_request(true)
+    }
+
+    /// Set the `supportsRestartFrame` capability. Clients only offer restarting frames whose
+    /// `canRestart` is not false (see [`StackFrame::with_can_restart`]) if it is set.
+    pub fn with_supports_restart_frame(mut self, value: bool) -> Self {
+        self.supports_restart_frame = Some(value);
+        self
+    }
+
+    /// Set the `supportsDataBreakpoints` capability. Clients only send `dataBreakpointInfo` and
+    /// `setDataBreakpoints` requests if it is set.
+    pub fn with_supports_data_breakpoints(mut self, value: bool) -> Self {
+        self.supports_data_breakpoints = Some(value);
+        self
+    }
+
+    /// Set the `supportsDataBreakpointBytes` capability. Clients only use the `asAddress` and
+    /// `bytes` fields of `dataBreakpointInfo` requests if it is set.
+    pub fn with_supports_data_breakpoint_bytes(mut self, value: bool) -> Self {
+        self.supports_data_breakpoint_bytes = Some(value);
+        self
+    }
+
+    /// Set the `supportsInstructionBreakpoints` capability. Clients only send
+    /// `setInstructionBreakpoints` requests if it is set.
+    pub fn with_supports_instruction_breakpoints(mut self, value: bool) -> Self {
+        self.supports_instruction_breakpoints = Some(value);
+        self
+    }
+
+    /// Set the `supportsTerminateThreadsRequest` capability. Clients only send `terminateThreads`
+    /// requests if it is set.
+    pub fn with_supports_terminate_threads_request(mut self, value: bool) -> Self {
+        self.supports_terminate_threads_request = Some(value);
+        self
+    }
+
+    /// Set the `supportsValueFormattingOptions` capability. Clients only send a `format` with
+    /// `variables`, `evaluate` and `stackTrace` requests if it is set.
+    pub fn with_supports_value_formatting_options(mut self, value: bool) -> Self {
+        self.supports_value_formatting_options = Some(value);
+        self
+    }
+
+    /// The value format to apply for a `variables` or `evaluate` request: the `requested` one if
+    /// `supportsValueFormattingOptions` was advertised, `None` (default formatting) otherwise.
+    pub fn value_format<'a>(&self, requested: Option<&'a ValueFormat>) -> Option<&'a ValueFormat> {
+        requested.filter(|_| self.supports_value_formatting_options == Some(true))
+    }
+
+    /// The stack frame format to apply for a `stackTrace` request, like
+    /// [`Capabilities::value_format`].
+    pub fn stack_frame_format<'a>(
+        &self,
+        requested: Option<&'a StackFrameFormat>,
+    ) -> Option<&'a StackFrameFormat> {
+        requested.filter(|_| self.supports_value_formatting_options == Some(true))
+    }
+
+    /// Set the `supportsClipboardContext` capability.
+    ///
+    /// Adapters that can render values in a copy-friendly form (e.g. without truncation) should
+    /// advertise this, so clients send `evaluate` requests with the `clipboard` context when the
+    /// user copies a value.
+    pub fn with_supports_clipboard_context(mut self, value: bool) -> Self {
+        self.supports_clipboard_context = Some(value);
+        self
+    }
+
+    /// Whether the adapter advertised support for `evaluate` requests in the given `context`.
+    ///
+    /// Clients should only use the `clipboard` context if `supportsClipboardContext` is true and
+    /// the `hover` context if `supportsEvaluateForHovers` is true. All other contexts are always
+    /// available.
+    pub fn supports_evaluate_context(&self, context: &EvaluateArgumentsContext) -> bool {
+        match context {
+            EvaluateArgumentsContext::Clipboard => self.supports_clipboard_context == Some(true),
+            EvaluateArgumentsContext::Hover => self.supports_evaluate_for_hovers == Some(true),
+            _ => true,
+        }
+    }
+
+    /// Advertise that values can be edited, through both `setVariable` and `setExpression`.
+    /// Clients disable value editing in their variables and watch views otherwise.
+    pub fn with_mutation_support(self) -> Self {
+        self.with_supports_set_variable(true)
+            .with_supports_set_expression(true)
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 pub struct CustomValue(pub Value);
 
@@ -196,7 +487,6 @@ pub struct CustomValue(pub Value);
 /// Specification: [Source](https://microsoft.github.io/debug-adapter-protocol/specification#Types_Source)
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct Source {
     /// The short name of the source. Every source returned from the debug adapter
     /// has a name.
@@ -233,9 +523,34 @@ pub struct Source {
     pub checksums: Option<Vec<Checksum>>,
 }
 
+impl Source {
+    /// The normalized `path` of this source, interpreted according to the `pathFormat` the client
+    /// announced in the `initialize` request. See [`PathFormat::parse_path`].
+    #[cfg(feature = "std")]
+    pub fn normalized_path(&self, format: &PathFormat) -> Option<PathBuf> {
+        self.path.as_deref().map(|path| format.parse_path(path))
+    }
+
+    /// Create a source for a file that is skipped on stepping (e.g. library code that is not
+    /// part of "Just My Code"). The source is named after the last component of `path` and
+    /// carries the `deemphasize` presentation hint so clients render it accordingly.
+    #[cfg(feature = "std")]
+    pub fn skipped(path: impl Into<String>) -> Self {
+        let path = path.into();
+        let name = std::path::Path::new(&path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        Source {
+            name,
+            path: Some(path),
+            presentation_hint: Some(PresentationHint::DeEmphasize),
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct SourceBreakpoint {
     /// The source line of the breakpoint or logpoint.
     pub line: i64,
@@ -268,7 +583,6 @@ pub struct SourceBreakpoint {
 /// setInstructionBreakpoints, or setDataBreakpoints requests.
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct Breakpoint {
     /// The identifier for the breakpoint. It is needed if breakpoint events are
     /// used to update or remove breakpoints.
@@ -314,16 +628,15 @@ pub struct Breakpoint {
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum PresentationHint {
     Normal,
     Emphasize,
+    #[serde(rename = "deemphasize")]
     DeEmphasize,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct Checksum {
     /// The algorithm used to calculate this checksum.
     pub algorithm: ChecksumAlgorithm,
@@ -364,7 +677,6 @@ pub struct ExceptionBreakpointsFilter {
 /// the setExceptionBreakpoints request.
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct ExceptionFilterOptions {
     /// ID of an exception filter returned by the `exceptionBreakpointFilters`
     /// capability.
@@ -380,11 +692,11 @@ pub struct ExceptionFilterOptions {
 /// break.
 ///
 /// Specification: [`ExceptionBreakMode`](https://microsoft.github.io/debug-adapter-protocol/specification#Types_ExceptionBreakMode)
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum ExceptionBreakMode {
     /// never breaks
+    #[default]
     Never,
     /// always breaks
     Always,
@@ -394,12 +706,6 @@ pub enum ExceptionBreakMode {
     UserUnhandled,
 }
 
-impl Default for ExceptionBreakMode {
-    fn default() -> Self {
-        ExceptionBreakMode::Never
-    }
-}
-
 /// An ExceptionPathSegment represents a segment in a path that is used to match leafs or nodes in
 /// a tree of exceptions.
 /// If a segment consists of more than one name, it matches the names provided if negate is false
@@ -408,7 +714,6 @@ impl Default for ExceptionBreakMode {
 /// Specification: [`ExceptionPathSegment`](https://microsoft.github.io/debug-adapter-protocol/specification#Types_ExceptionPathSegment)
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct ExceptionPathSegment {
     /// If false or missing this segment matches the names provided, otherwise it
     /// matches anything except the names provided.
@@ -424,7 +729,6 @@ pub struct ExceptionPathSegment {
 /// Specification: [`ExceptionOptions`](https://microsoft.github.io/debug-adapter-protocol/specification#Types_ExceptionOptions)
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct ExceptionOptions {
     /// A path that selects a single or multiple exceptions in a tree. If `path` is
     /// missing, the whole tree is selected.
@@ -441,7 +745,6 @@ pub struct ExceptionOptions {
 /// Specification: [FunctionBreakpoint](https://microsoft.github.io/debug-adapter-protocol/specification#Types_FunctionBreakpoint)
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct FunctionBreakpoint {
     /// The name of the function.
     pub name: String,
@@ -458,9 +761,30 @@ pub struct FunctionBreakpoint {
     pub hit_condition: Option<String>,
 }
 
+impl FunctionBreakpoint {
+    /// A breakpoint on entry to the function called `name`.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Only break when `condition` evaluates to true.
+    pub fn with_condition(mut self, condition: impl Into<String>) -> Self {
+        self.condition = Some(condition.into());
+        self
+    }
+
+    /// Control how many hits of the breakpoint are ignored.
+    pub fn with_hit_condition(mut self, hit_condition: impl Into<String>) -> Self {
+        self.hit_condition = Some(hit_condition.into());
+        self
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum BreakpointEventReason {
     Changed,
     New,
@@ -471,7 +795,6 @@ pub enum BreakpointEventReason {
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum InvalidatedAreas {
     All,
     Stacks,
@@ -483,16 +806,14 @@ pub enum InvalidatedAreas {
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum LoadedSourceEventReason {
     New,
     Changed,
     Removed,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
-
 pub enum ModuleEventReason {
     New,
     Changed,
@@ -501,7 +822,6 @@ pub enum ModuleEventReason {
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct Module {
     /// Unique identifier for the module.
     pub id: ModuleId,
@@ -538,9 +858,8 @@ pub struct Module {
     pub address_range: Option<String>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
-
 pub enum ModuleId {
     Number,
     #[serde(untagged)]
@@ -549,7 +868,6 @@ pub enum ModuleId {
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum OutputEventCategory {
     Console,
     Important,
@@ -562,7 +880,6 @@ pub enum OutputEventCategory {
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum OutputEventGroup {
     Start,
     StartCollapsed,
@@ -571,7 +888,6 @@ pub enum OutputEventGroup {
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum ProcessEventStartMethod {
     Launch,
     Attach,
@@ -580,7 +896,6 @@ pub enum ProcessEventStartMethod {
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum StoppedEventReason {
     Step,
     Breakpoint,
@@ -597,7 +912,6 @@ pub enum StoppedEventReason {
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum ThreadEventReason {
     Started,
     Exited,
@@ -607,7 +921,6 @@ pub enum ThreadEventReason {
 
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct ValueFormat {
     /// Display the value in hex.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -641,9 +954,37 @@ pub struct StackFrameFormat {
     pub include_all: Option<bool>,
 }
 
+impl StackFrameFormat {
+    /// Show everything about a frame: its parameters with their names, types and values, the
+    /// line and the module.
+    pub fn verbose() -> Self {
+        Self {
+            parameters: Some(true),
+            parameter_types: Some(true),
+            parameter_names: Some(true),
+            parameter_values: Some(true),
+            line: Some(true),
+            module: Some(true),
+            include_all: None,
+        }
+    }
+
+    /// Show only the frame's name, without parameters, line or module.
+    pub fn compact() -> Self {
+        Self {
+            parameters: Some(false),
+            parameter_types: Some(false),
+            parameter_names: Some(false),
+            parameter_values: Some(false),
+            line: Some(false),
+            module: Some(false),
+            include_all: None,
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum EvaluateArgumentsContext {
     Variables,
     Watch,
@@ -654,18 +995,25 @@ pub enum EvaluateArgumentsContext {
     String(String),
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+/// The granularity of one 'step' in the stepping requests `next`, `stepIn`, `stepOut`, and
+/// `stepBack`.
+///
+/// Specification: [SteppingGranularity](https://microsoft.github.io/debug-adapter-protocol/specification#Types_SteppingGranularity)
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
-
 pub enum SteppingGranularity {
+    /// The step should allow the program to run until the current statement has finished
+    /// executing. This is the default.
+    #[default]
     Statement,
+    /// The step should allow the program to run until the current source line has executed.
     Line,
+    /// The step should allow one instruction to execute (e.g. one x86 instruction).
     Instruction,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum DataBreakpointAccessType {
     Read,
     Write,
@@ -674,7 +1022,6 @@ pub enum DataBreakpointAccessType {
 
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct DataBreakpoint {
     /// An id representing the data. This id is returned from the
     /// `dataBreakpointInfo` request.
@@ -696,7 +1043,6 @@ pub struct DataBreakpoint {
 /// Specfication: [InstructionBreakpoint](https://microsoft.github.io/debug-adapter-protocol/specification#Types_InstructionBreakpoint)
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct InstructionBreakpoint {
     /// The instruction reference of the breakpoint.
     /// This should be a memory or instruction pointer reference from an
@@ -722,18 +1068,16 @@ pub struct InstructionBreakpoint {
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum VariablesArgumentsFilter {
     Indexed,
     Named,
 }
 
 /// Properties of a breakpoint location returned from the breakpointLocations request.
-
+///
 /// Specfication: [BreakpointLocation](https://microsoft.github.io/debug-adapter-protocol/specification#Types_BreakpointLocation)
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct BreakpointLocation {
     /// Start line of breakpoint location.
     pub line: i64,
@@ -752,13 +1096,35 @@ pub struct BreakpointLocation {
     pub end_column: Option<i64>,
 }
 
+impl BreakpointLocation {
+    /// A breakpoint location covering the whole `line`.
+    pub fn new(line: i64) -> Self {
+        Self {
+            line,
+            ..Default::default()
+        }
+    }
+
+    /// Set the start column of the location.
+    pub fn with_column(mut self, column: i64) -> Self {
+        self.column = Some(column);
+        self
+    }
+
+    /// Make the location cover a range ending at `end_line`/`end_column`.
+    pub fn with_end(mut self, end_line: i64, end_column: Option<i64>) -> Self {
+        self.end_line = Some(end_line);
+        self.end_column = end_column;
+        self
+    }
+}
+
 /// Some predefined types for the CompletionItem. Please note that not all clients have specific
 /// icons for all of them
 ///
 /// Specification: [CompletionItemType](https://microsoft.github.io/debug-adapter-protocol/specification#Types_CompletionItemType)
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum CompletionItemType {
     Method,
     Function,
@@ -786,7 +1152,6 @@ pub enum CompletionItemType {
 /// Specification: [CompletionItem](https://microsoft.github.io/debug-adapter-protocol/specification#Types_CompletionItem)
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct CompletionItem {
     /// The label of this completion item. By default this is also the text that is
     /// inserted when selecting this completion.
@@ -839,7 +1204,6 @@ pub struct CompletionItem {
 /// Specification: [DisassembledInstruction](https://microsoft.github.io/debug-adapter-protocol/specification#Types_DisassembledInstruction)
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct DisassembledInstruction {
     /// The address of the instruction. Treated as a hex value if prefixed with
     /// `0x`, or as a decimal value otherwise.
@@ -876,9 +1240,43 @@ pub struct DisassembledInstruction {
     pub end_column: Option<i64>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
-#[serde(rename_all = "camelCase")]
+impl DisassembledInstruction {
+    /// The instruction at `address` (formatted by
+    /// [`memory_reference_from_addr`](crate::utils::memory_reference_from_addr)), with
+    /// `instruction` as its text, e.g. `mov rbp, rsp`.
+    pub fn new(address: u64, instruction: impl Into<String>) -> Self {
+        Self {
+            address: crate::utils::memory_reference_from_addr(address),
+            instruction: instruction.into(),
+            ..Default::default()
+        }
+    }
 
+    /// Set the raw bytes of the instruction, shown as space separated hex pairs.
+    pub fn with_bytes(mut self, bytes: &[u8]) -> Self {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+        self.instruction_bytes = Some(hex.join(" "));
+        self
+    }
+
+    /// Set the name of the symbol the instruction belongs to.
+    pub fn with_symbol(mut self, symbol: impl Into<String>) -> Self {
+        self.symbol = Some(symbol.into());
+        self
+    }
+
+    /// Mark the instruction as generated from `line` (and `column`, if known) of `source`, so the
+    /// client can show the disassembly next to the source.
+    pub fn with_location(mut self, source: Source, line: i64, column: Option<i64>) -> Self {
+        self.location = Some(source);
+        self.line = Some(line);
+        self.column = column;
+        self
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
 pub enum VariablePresentationHintKind {
     /// Indicates that the object is a property.
     Property,
@@ -912,9 +1310,8 @@ pub enum VariablePresentationHintKind {
 
 /// Set of attributes represented as an array of Strings. Before introducing
 /// additional values, try to use the listed values.
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
-
 pub enum VariablePresentationHintAttributes {
     /// Indicates that the object is static.
     Static,
@@ -936,9 +1333,8 @@ pub enum VariablePresentationHintAttributes {
     String(String),
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
-
 pub enum VariablePresentationHintVisibility {
     Public,
     Private,
@@ -949,7 +1345,7 @@ pub enum VariablePresentationHintVisibility {
     String(String),
 }
 
-#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct VariablePresentationHint {
     /// The kind of variable. Before introducing additional values, try to use the
@@ -1013,7 +1409,6 @@ pub struct VariablePresentationHint {
 /// Specification: [ExceptionDetails](https://microsoft.github.io/debug-adapter-protocol/specification#Types_ExceptionDetails)
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct ExceptionDetails {
     /// Message contained in the exception.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -1043,7 +1438,6 @@ pub struct ExceptionDetails {
 /// Specification: [GotoTarget](https://microsoft.github.io/debug-adapter-protocol/specification#Types_GotoTarget)
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct GotoTarget {
     /// Unique identifier for a goto target. This is used in the `goto` request.
     pub id: i64,
@@ -1066,13 +1460,33 @@ pub struct GotoTarget {
     pub instruction_pointer_reference: Option<String>,
 }
 
+impl GotoTarget {
+    /// A goto target at `line`, shown to the user as `label`.
+    pub fn new(id: i64, label: impl Into<String>, line: i64) -> Self {
+        Self {
+            id,
+            label: label.into(),
+            line,
+            ..Default::default()
+        }
+    }
+
+    /// The arguments of the `goto` request that moves `thread_id` to this target, the step
+    /// following a `gotoTargets` request.
+    pub fn goto_arguments(&self, thread_id: i64) -> GotoArguments {
+        GotoArguments {
+            thread_id,
+            target_id: self.id,
+        }
+    }
+}
+
 /// A hint for how to present this scope in the UI. If this attribute is
 /// missing, the scope is shown with a generic UI.
 ///
 /// Specification: [Scope](https://microsoft.github.io/debug-adapter-protocol/specification#Types_Scope)
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum ScopePresentationhint {
     /// Scope contains method arguments.
     Arguments,
@@ -1091,7 +1505,6 @@ pub enum ScopePresentationhint {
 /// Specification: [Scope](https://microsoft.github.io/debug-adapter-protocol/specification#Types_Scope)
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct Scope {
     /// Name of the scope such as 'Arguments', 'Locals', or 'Registers'. This
     /// String is shown in the UI as is and can be translated.
@@ -1147,7 +1560,6 @@ pub struct Scope {
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(untagged)]
-
 pub enum StackFrameModuleid {
     Number(i64),
     String(String),
@@ -1155,7 +1567,6 @@ pub enum StackFrameModuleid {
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum StackFramePresentationhint {
     Normal,
     Label,
@@ -1167,7 +1578,6 @@ pub enum StackFramePresentationhint {
 /// Specification: [StackFrame](https://microsoft.github.io/debug-adapter-protocol/specification#Types_StackFrame)
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct StackFrame {
     /// An identifier for the stack frame. It must be unique across all threads.
     /// This id can be used to retrieve the scopes of the frame with the `scopes`
@@ -1214,12 +1624,69 @@ pub struct StackFrame {
     pub presentation_hint: Option<StackFramePresentationhint>,
 }
 
+impl StackFrame {
+    /// Create a frame without location information. Use [`StackFrame::with_location`] to
+    /// attach a source position.
+    pub fn new(id: i64, name: impl Into<String>) -> Self {
+        StackFrame {
+            id,
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the source and the position of the frame within it.
+    pub fn with_location(mut self, source: Source, line: i64, column: i64) -> Self {
+        self.source = Some(source);
+        self.line = line;
+        self.column = column;
+        self
+    }
+
+    /// Set the end of the range covered by the frame, so clients highlight the whole statement
+    /// instead of a single position. Call this after [`StackFrame::with_location`].
+    ///
+    /// Returns [`ValidationError::InvalidRange`] if the end lies before the frame's start position.
+    pub fn with_end(mut self, end_line: i64, end_column: i64) -> Result<Self, ValidationError> {
+        if (end_line, end_column) < (self.line, self.column) {
+            return Err(ValidationError::InvalidRange {
+                reason: format!(
+                    "frame '{}' ends at {end_line}:{end_column}, before its start at {}:{}",
+                    self.name, self.line, self.column
+                ),
+            });
+        }
+        self.end_line = Some(end_line);
+        self.end_column = Some(end_column);
+        Ok(self)
+    }
+
+    /// Set whether the frame can be restarted with the `restartFrame` request. Only meaningful if
+    /// the adapter advertised `supportsRestartFrame`.
+    pub fn with_can_restart(mut self, can_restart: bool) -> Self {
+        self.can_restart = Some(can_restart);
+        self
+    }
+
+    /// Set the memory reference for the current instruction pointer in this frame. Clients use
+    /// this to place the cursor in the disassembly view.
+    pub fn with_instruction_pointer_reference(mut self, reference: impl Into<String>) -> Self {
+        self.instruction_pointer_reference = Some(reference.into());
+        self
+    }
+
+    /// Set the instruction pointer reference from a raw address, see
+    /// [`memory_reference_from_addr`](crate::utils::memory_reference_from_addr).
+    pub fn with_instruction_pointer(self, address: u64) -> Self {
+        self.with_instruction_pointer_reference(crate::utils::memory_reference_from_addr(address))
+    }
+}
+
 /// A thread.
 ///
 /// Specification: [Thread](https://microsoft.github.io/debug-adapter-protocol/specification#Types_Thread)
 #[derive(Deserialize, Serialize, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub struct Thread {
     /// Unique identifier for the thread.
     pub id: i64,
@@ -1227,6 +1694,18 @@ pub struct Thread {
     pub name: String,
 }
 
+impl Thread {
+    /// Create a thread whose name is prefixed with the name of the process it belongs to, e.g.
+    /// `server: main`. This keeps threads distinguishable when several processes are debugged
+    /// in the same session.
+    pub fn with_process(id: i64, process_name: &str, thread_name: &str) -> Self {
+        Thread {
+            id,
+            name: format!("{process_name}: {thread_name}"),
+        }
+    }
+}
+
 /// A Variable is a name/value pair.
 ///
 /// The `type` attribute is shown if space permits or when hovering over the variable’s name.
@@ -1242,9 +1721,8 @@ pub struct Thread {
 ///
 /// The client can use this information to present the children in a paged UI and fetch them in
 /// chunks.
-#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
-
 pub struct Variable {
     /// The variable's name.
     pub name: String,
@@ -1291,11 +1769,159 @@ pub struct Variable {
     /// `supportsMemoryReferences` is true.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub memory_reference: Option<String>,
+    /// A reference that allows the client to request the location where the
+    /// variable is declared. This should be present only if the adapter is likely
+    /// to be able to resolve the location.
+    /// This reference shares the same lifetime as the `variablesReference`. See
+    /// [Lifetime of Object References](https://microsoft.github.io/debug-adapter-protocol/overview#lifetime-of-objects-references)
+    /// in the Overview section of the specification for details.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub declaration_location_reference: Option<i64>,
+    /// A reference that allows the client to request the location where the
+    /// variable's value is declared. For example, if the variable contains a
+    /// function pointer, the adapter may be able to look up the function's
+    /// location. This should be present only if the adapter is likely to be able
+    /// to resolve the location.
+    /// This reference shares the same lifetime as the `variablesReference`. See
+    /// [Lifetime of Object References](https://microsoft.github.io/debug-adapter-protocol/overview#lifetime-of-objects-references)
+    /// in the Overview section of the specification for details.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_location_reference: Option<i64>,
+}
+
+impl Variable {
+    /// Create a simple (unstructured) variable with the given name and value.
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Variable {
+            name: name.into(),
+            value: value.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Create a placeholder for a missing element of an indexed variable, named `[index]` with an
+    /// empty value, so the indices of the elements around it stay contiguous.
+    pub fn hole(index: i64) -> Self {
+        Variable::new(format!("[{index}]"), "")
+    }
+
+    /// Create a variable whose value is a raw string, i.e. it is shown as is, without the quotes
+    /// and escaping clients usually apply to string values.
+    pub fn raw_string(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Variable {
+            presentation_hint: Some(VariablePresentationHint {
+                attributes: Some(vec![VariablePresentationHintAttributes::RawString]),
+                ..Default::default()
+            }),
+            ..Variable::new(name, value)
+        }
+    }
+
+    /// Set the type of the variable's value.
+    pub fn with_type(mut self, type_name: impl Into<String>) -> Self {
+        self.type_field = Some(type_name.into());
+        self
+    }
+
+    /// Set the type of the variable's value to the name of the Rust type `T`, see
+    /// [`type_name_of`](crate::utils::type_name_of).
+    pub fn with_type_of<T: ?Sized>(self) -> Self {
+        self.with_type(crate::utils::type_name_of::<T>())
+    }
+
+    /// Set the visibility (access modifier) of an object member, keeping any other presentation
+    /// hints.
+    pub fn with_visibility(mut self, visibility: VariablePresentationHintVisibility) -> Self {
+        self.presentation_hint
+            .get_or_insert_with(Default::default)
+            .visibility = Some(visibility);
+        self
+    }
+
+    /// Adjust the variable to what the client announced in its `initialize` request: the `type`
+    /// is dropped unless the client set `supportsVariableType`.
+    pub fn for_client(mut self, client: &InitializeArguments) -> Self {
+        if client.supports_variable_type != Some(true) {
+            self.type_field = None;
+        }
+        self
+    }
+
+    /// Set the reference the client can resolve with the `locations` request to find where this
+    /// variable is declared (see [`LocationRegistry`](crate::locations::LocationRegistry)).
+    pub fn with_declaration_location_reference(mut self, reference: i64) -> Self {
+        self.declaration_location_reference = Some(reference);
+        self
+    }
+
+    /// Set the reference the client can resolve with the `locations` request to find where the
+    /// value of this variable is declared, e.g. the definition of a function value.
+    pub fn with_value_location_reference(mut self, reference: i64) -> Self {
+        self.value_location_reference = Some(reference);
+        self
+    }
+
+    /// Set the memory reference of a pointer-typed variable from the address it points to, see
+    /// [`memory_reference_from_addr`](crate::utils::memory_reference_from_addr).
+    pub fn with_memory_address(mut self, addr: u64) -> Self {
+        self.memory_reference = Some(crate::utils::memory_reference_from_addr(addr));
+        self
+    }
+
+    /// Set both the memory reference (to inspect the pointee's bytes) and the value location
+    /// reference (to navigate to where the value is declared) of a pointer-typed variable.
+    ///
+    /// Returns [`ValidationError::InvalidReference`] if `memory_reference` is empty or
+    /// `value_location_reference` is not a valid (positive) reference.
+    pub fn with_pointer_references(
+        mut self,
+        memory_reference: impl Into<String>,
+        value_location_reference: i64,
+    ) -> Result<Self, ValidationError> {
+        let memory_reference = memory_reference.into();
+        if memory_reference.trim().is_empty() {
+            return Err(ValidationError::InvalidReference {
+                reason: format!("empty memory reference for variable '{}'", self.name),
+            });
+        }
+        if value_location_reference <= 0 {
+            return Err(ValidationError::InvalidReference {
+                reason: format!(
+                    "value location reference {value_location_reference} for variable '{}'",
+                    self.name
+                ),
+            });
+        }
+        self.memory_reference = Some(memory_reference);
+        self.value_location_reference = Some(value_location_reference);
+        Ok(self)
+    }
+
+    /// The synthetic child an adapter adds below a pointer variable to show the pointee.
+    ///
+    /// By convention the child is named `*<name>` after this variable, its evaluate name
+    /// dereferences this variable's evaluate name (or its name, if it has none) and expanding it
+    /// resolves `target_reference`.
+    pub fn deref_child(&self, target_reference: i64, type_name: impl Into<String>) -> Variable {
+        let pointer = self.evaluate_name.as_deref().unwrap_or(&self.name);
+        let is_simple = pointer
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.');
+        let evaluate_name = if is_simple {
+            format!("*{pointer}")
+        } else {
+            format!("*({pointer})")
+        };
+        Variable {
+            evaluate_name: Some(evaluate_name),
+            variables_reference: target_reference,
+            ..Variable::new(format!("*{}", self.name), "").with_type(type_name)
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum RunInTerminalRequestArgumentsKind {
     Integrated,
     External,
@@ -1303,7 +1929,6 @@ pub enum RunInTerminalRequestArgumentsKind {
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-
 pub enum StartDebuggingRequestKind {
     Launch,
     Attach,
@@ -1326,19 +1951,56 @@ pub struct Message {
     /// contain user data (PII) and can be safely used for telemetry purposes.
     pub format: String,
     /// An object used as a dictionary for looking up the variables in the format string.
-    pub variables: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, String>,
     /// An object used as a dictionary for looking up the variables in the format
     /// String.
     /// If true send to telemetry.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub send_telemetry: Option<bool>,
     /// If true show user.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub show_user: Option<bool>,
     /// A url where additional information about this message can be found.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<String>,
     /// A label that is presented to the user as the UI for opening the url.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub url_label: Option<String>,
 }
 
+impl Message {
+    /// A message with the given `id` and `format` string.
+    pub fn new(id: i64, format: impl Into<String>) -> Self {
+        Message {
+            id,
+            format: format.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the value of the `{name}` variable in the format string.
+    pub fn with_variable(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+
+    /// Whether the client should show the message to the user.
+    pub fn with_show_user(mut self, show: bool) -> Self {
+        self.show_user = Some(show);
+        self
+    }
+
+    /// The format string with its `{name}` variables replaced by their values.
+    pub fn formatted(&self) -> String {
+        self.variables
+            .iter()
+            .fold(self.format.clone(), |text, (name, value)| {
+                text.replace(&format!("{{{name}}}"), value)
+            })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1370,4 +2032,339 @@ mod tests {
         let untagged_deser: InvalidatedAreas = serde_json::from_value(untagged_ser).unwrap();
         assert!(matches!(InvalidatedAreas::String(str), untagged_deser));
     }
+
+    #[test]
+    fn test_skipped_source() {
+        let source = Source::skipped("/usr/lib/lua/5.4/inspect.lua");
+        assert_eq!(source.name.as_deref(), Some("inspect.lua"));
+        assert_eq!(source.path.as_deref(), Some("/usr/lib/lua/5.4/inspect.lua"));
+        assert!(matches!(
+            source.presentation_hint,
+            Some(PresentationHint::DeEmphasize)
+        ));
+
+        let val = serde_json::to_value(&source).unwrap();
+        assert_eq!(val["presentationHint"], "deemphasize");
+    }
+
+    #[test]
+    fn test_thread_with_process() {
+        let thread = Thread::with_process(7, "worker", "main");
+        assert_eq!(thread.id, 7);
+        assert_eq!(thread.name, "worker: main");
+    }
+
+    #[test]
+    fn test_capabilities_mutation_support() {
+        let caps = Capabilities::default().with_mutation_support();
+        assert_eq!(caps.supports_set_variable, Some(true));
+        assert_eq!(caps.supports_set_expression, Some(true));
+
+        let caps = caps.with_supports_set_expression(false);
+        assert_eq!(caps.supports_set_variable, Some(true));
+        assert_eq!(caps.supports_set_expression, Some(false));
+    }
+
+    #[test]
+    fn test_capabilities_clipboard_context() {
+        let caps = Capabilities::default();
+        assert!(!caps.supports_evaluate_context(&EvaluateArgumentsContext::Clipboard));
+        assert!(caps.supports_evaluate_context(&EvaluateArgumentsContext::Repl));
+
+        let caps = caps.with_supports_clipboard_context(true);
+        assert!(caps.supports_evaluate_context(&EvaluateArgumentsContext::Clipboard));
+        let val = serde_json::to_value(&caps).unwrap();
+        assert_eq!(val["supportsClipboardContext"], true);
+    }
+
+    #[test]
+    fn test_capabilities_evaluate_for_hovers() {
+        let caps = Capabilities::default();
+        assert!(!caps.supports_evaluate_context(&EvaluateArgumentsContext::Hover));
+
+        let caps = caps.with_supports_evaluate_for_hovers(true);
+        assert!(caps.supports_evaluate_context(&EvaluateArgumentsContext::Hover));
+        assert!(!caps.supports_evaluate_context(&EvaluateArgumentsContext::Clipboard));
+        let val = serde_json::to_value(&caps).unwrap();
+        assert_eq!(val["supportsEvaluateForHovers"], true);
+    }
+
+    #[test]
+    fn test_capabilities_stepping_granularity() {
+        let caps = Capabilities::default();
+        assert_eq!(
+            caps.stepping_granularity(Some(SteppingGranularity::Instruction)),
+            SteppingGranularity::Statement
+        );
+
+        let caps = caps.with_supports_stepping_granularity(true);
+        assert_eq!(
+            caps.stepping_granularity(Some(SteppingGranularity::Instruction)),
+            SteppingGranularity::Instruction
+        );
+        assert_eq!(
+            caps.stepping_granularity(None),
+            SteppingGranularity::Statement
+        );
+        let val = serde_json::to_value(&caps).unwrap();
+        assert_eq!(val["supportsSteppingGranularity"], true);
+    }
+
+    #[test]
+    fn test_capabilities_value_formatting_options() {
+        let hex = ValueFormat { hex: Some(true) };
+        let frame_format = StackFrameFormat {
+            parameters: Some(true),
+            ..Default::default()
+        };
+
+        let caps = Capabilities::default();
+        assert!(caps.value_format(Some(&hex)).is_none());
+        assert!(caps.stack_frame_format(Some(&frame_format)).is_none());
+
+        let caps = caps.with_supports_value_formatting_options(true);
+        assert_eq!(
+            caps.value_format(Some(&hex)).and_then(|f| f.hex),
+            Some(true)
+        );
+        assert!(caps.stack_frame_format(Some(&frame_format)).is_some());
+        assert!(caps.value_format(None).is_none());
+    }
+
+    #[test]
+    fn test_stack_frame_instruction_pointer() {
+        let frame = StackFrame::new(1, "update")
+            .with_location(Source::default(), 42, 1)
+            .with_instruction_pointer(0x7f3a_0000_1c40);
+        assert_eq!(
+            frame.instruction_pointer_reference.as_deref(),
+            Some("0x7f3a00001c40")
+        );
+
+        let val = serde_json::to_value(&frame).unwrap();
+        assert_eq!(val["instructionPointerReference"], "0x7f3a00001c40");
+        assert_eq!(val["line"], 42);
+    }
+
+    #[test]
+    fn test_stack_frame_range() {
+        let frame = StackFrame::new(1, "update")
+            .with_location(Source::default(), 10, 5)
+            .with_end(12, 3)
+            .unwrap();
+        let val = serde_json::to_value(&frame).unwrap();
+        assert_eq!(val["endLine"], 12);
+        assert_eq!(val["endColumn"], 3);
+
+        let inverted = StackFrame::new(1, "update")
+            .with_location(Source::default(), 10, 5)
+            .with_end(10, 2);
+        assert!(matches!(
+            inverted,
+            Err(ValidationError::InvalidRange { .. })
+        ));
+    }
+
+    #[test]
+    fn test_capabilities_memory_support() {
+        let caps = Capabilities::default().with_memory_support();
+        assert_eq!(caps.supports_read_memory_request, Some(true));
+        assert_eq!(caps.supports_write_memory_request, Some(true));
+        assert_eq!(caps.supports_disassemble_request, Some(true));
+    }
+
+    #[test]
+    fn test_capabilities_advanced_breakpoints() {
+        let caps = Capabilities::default().with_advanced_breakpoints();
+        let val = serde_json::to_value(&caps).unwrap();
+        assert_eq!(val["supportsConditionalBreakpoints"], true);
+        assert_eq!(val["supportsHitConditionalBreakpoints"], true);
+        assert_eq!(val["supportsLogPoints"], true);
+
+        let caps = caps.with_supports_log_points(false);
+        assert_eq!(caps.supports_conditional_breakpoints, Some(true));
+        assert_eq!(caps.supports_log_points, Some(false));
+    }
+
+    #[test]
+    fn test_variable_deref_child() {
+        let pointer = Variable::new("node", "0x7ffd1c40").with_type("Node *");
+        let child = pointer.deref_child(17, "Node");
+        assert_eq!(child.name, "*node");
+        assert_eq!(child.evaluate_name.as_deref(), Some("*node"));
+        assert_eq!(child.variables_reference, 17);
+        assert_eq!(child.type_field.as_deref(), Some("Node"));
+
+        let field = Variable {
+            evaluate_name: Some("list->head".to_string()),
+            ..Variable::new("head", "0x7ffd1c80")
+        };
+        let child = field.deref_child(18, "Node");
+        assert_eq!(child.name, "*head");
+        assert_eq!(child.evaluate_name.as_deref(), Some("*(list->head)"));
+    }
+
+    #[test]
+    fn test_capabilities_goto_targets() {
+        let caps = Capabilities::default().with_supports_goto_targets_request(true);
+        let val = serde_json::to_value(&caps).unwrap();
+        assert_eq!(val["supportsGotoTargetsRequest"], true);
+
+        let target = GotoTarget::new(7, "line 42", 42);
+        let args = target.goto_arguments(3);
+        assert_eq!(args.thread_id, 3);
+        assert_eq!(args.target_id, 7);
+    }
+
+    #[test]
+    fn test_variable_pointer_references() {
+        let variable = Variable::new("callback", "0x55d0c0de")
+            .with_pointer_references("0x55d0c0de", 4)
+            .unwrap();
+        let val = serde_json::to_value(&variable).unwrap();
+        assert_eq!(val["memoryReference"], "0x55d0c0de");
+        assert_eq!(val["valueLocationReference"], 4);
+
+        assert!(matches!(
+            Variable::new("callback", "nil").with_pointer_references("", 4),
+            Err(ValidationError::InvalidReference { .. })
+        ));
+        assert!(matches!(
+            Variable::new("callback", "0x1").with_pointer_references("0x1", 0),
+            Err(ValidationError::InvalidReference { .. })
+        ));
+    }
+
+    #[test]
+    fn test_variable_visibility() {
+        let variable = Variable::raw_string("secret", "hunter2")
+            .with_visibility(VariablePresentationHintVisibility::Private);
+        let val = serde_json::to_value(&variable).unwrap();
+        assert_eq!(val["presentationHint"]["visibility"], "private");
+        assert_eq!(
+            val["presentationHint"]["attributes"],
+            serde_json::json!(["rawString"])
+        );
+    }
+
+    #[test]
+    fn test_variable_memory_address() {
+        let variable = Variable::new("buf", "0x7fff5fbff8a0").with_memory_address(0x7fff_5fbf_f8a0);
+        let val = serde_json::to_value(&variable).unwrap();
+        assert_eq!(val["memoryReference"], "0x7fff5fbff8a0");
+    }
+
+    #[test]
+    fn test_variable_raw_string() {
+        let variable = Variable::raw_string("log", "line 1\nline 2");
+        let attributes = variable
+            .presentation_hint
+            .as_ref()
+            .and_then(|hint| hint.attributes.as_ref())
+            .unwrap();
+        assert!(matches!(
+            attributes.as_slice(),
+            [VariablePresentationHintAttributes::RawString]
+        ));
+
+        let val = serde_json::to_value(&variable).unwrap();
+        assert_eq!(
+            val["presentationHint"]["attributes"],
+            serde_json::json!(["rawString"])
+        );
+    }
+
+    #[test]
+    fn test_variable_tree_clone_and_compare() {
+        let hint = VariablePresentationHint {
+            kind: Some(VariablePresentationHintKind::Property),
+            attributes: Some(vec![VariablePresentationHintAttributes::ReadOnly]),
+            visibility: Some(VariablePresentationHintVisibility::Private),
+            ..Default::default()
+        };
+        let player = Variable {
+            name: "player".to_string(),
+            value: "table: 0x55d0".to_string(),
+            variables_reference: 7,
+            named_variables: Some(2),
+            ..Default::default()
+        };
+        let children = vec![
+            Variable {
+                name: "hp".to_string(),
+                value: "100".to_string(),
+                presentation_hint: Some(hint.clone()),
+                ..Default::default()
+            },
+            Variable {
+                name: "name".to_string(),
+                value: "\"hero\"".to_string(),
+                presentation_hint: Some(hint),
+                ..Default::default()
+            },
+        ];
+        let tree = vec![(player, children)];
+
+        let cached = tree.clone();
+        assert_eq!(cached, tree);
+
+        let mut changed = tree.clone();
+        changed[0].1[0].value = "90".to_string();
+        assert_ne!(changed, cached);
+
+        let mut rehinted = tree.clone();
+        rehinted[0].1[1]
+            .presentation_hint
+            .as_mut()
+            .unwrap()
+            .visibility = Some(VariablePresentationHintVisibility::Public);
+        assert_ne!(rehinted, cached);
+    }
+
+    #[test]
+    fn test_stack_frame_format_presets() {
+        let verbose = serde_json::to_value(StackFrameFormat::verbose()).unwrap();
+        assert_eq!(
+            verbose,
+            serde_json::json!({
+                "parameters": true,
+                "parameterTypes": true,
+                "parameterNames": true,
+                "parameterValues": true,
+                "line": true,
+                "module": true,
+            })
+        );
+        let compact = serde_json::to_value(StackFrameFormat::compact()).unwrap();
+        assert_eq!(
+            compact,
+            serde_json::json!({
+                "parameters": false,
+                "parameterTypes": false,
+                "parameterNames": false,
+                "parameterValues": false,
+                "line": false,
+                "module": false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_capabilities_presets() {
+        let basic = Capabilities::basic_interpreter();
+        assert_eq!(basic.supports_conditional_breakpoints, Some(true));
+        assert_eq!(basic.supports_evaluate_for_hovers, Some(true));
+        assert_eq!(basic.supports_read_memory_request, None);
+        assert_eq!(basic.supports_disassemble_request, None);
+
+        let native = Capabilities::native_debugger();
+        assert_eq!(native.supports_read_memory_request, Some(true));
+        assert_eq!(native.supports_write_memory_request, Some(true));
+        assert_eq!(native.supports_disassemble_request, Some(true));
+        assert_eq!(native.supports_instruction_breakpoints, Some(true));
+        assert_eq!(native.supports_conditional_breakpoints, Some(true));
+
+        let adjusted = Capabilities::native_debugger().with_supports_write_memory_request(false);
+        assert_eq!(adjusted.supports_write_memory_request, Some(false));
+    }
 }
@@ -1,4 +1,8 @@
-use std::fmt::{Display, Formatter};
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+use core::fmt::{Display, Formatter};
 
 /// A struct representing a version of the DAP specification.
 /// This version corresponds to the [changelog](https://microsoft.github.io/debug-adapter-protocol/changelog)
@@ -19,7 +23,7 @@ pub struct Version {
 }
 
 impl Display for Version {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self.patch {
             Some(patch) => write!(f, "{}.{}.{}", self.major, self.minor, patch),
             None => write!(f, "{}.{}.x", self.major, self.minor),
@@ -27,6 +31,24 @@ impl Display for Version {
     }
 }
 
+impl Version {
+    /// Whether these types model everything a peer implementing `client_version` (e.g. `1.65.0`
+    /// or `1.65.x`) may send, i.e. that version is not newer than this one.
+    ///
+    /// Only the major and minor version are compared, as patch releases of the specification
+    /// don't add protocol surface. Returns `false` for strings that are not a version.
+    pub fn is_compatible_with(&self, client_version: &str) -> bool {
+        let mut parts = client_version.trim().split('.');
+        let (Some(Ok(major)), Some(Ok(minor))) = (
+            parts.next().map(str::parse::<i64>),
+            parts.next().map(str::parse::<i64>),
+        ) else {
+            return false;
+        };
+        (major, minor) <= (self.major, self.minor)
+    }
+}
+
 /// Returns the version of the DAP specification that this crate implements.
 ///
 /// Please note that historically, the DAP changelog hasn't been super accurate and the
@@ -41,6 +63,41 @@ pub fn get_spec_version() -> Version {
     }
 }
 
+/// Format a type name the way Rust writes it, e.g. `std::collections::HashMap<String, i64>`, for
+/// use as the `type` of a variable.
+///
+/// `module` may be empty for types that should be shown unqualified; `generics` are the already
+/// formatted type arguments.
+pub fn format_type(module: &str, name: &str, generics: &[&str]) -> String {
+    let mut formatted = if module.is_empty() {
+        name.to_string()
+    } else {
+        format!("{module}::{name}")
+    };
+    if !generics.is_empty() {
+        formatted.push('<');
+        formatted.push_str(&generics.join(", "));
+        formatted.push('>');
+    }
+    formatted
+}
+
+/// The name of the Rust type `T`, e.g. `alloc::vec::Vec<i32>`.
+///
+/// This is [`std::any::type_name`], so the exact output is not guaranteed to be stable across
+/// compiler versions. Use it for display purposes only.
+pub fn type_name_of<T: ?Sized>() -> &'static str {
+    core::any::type_name::<T>()
+}
+
+/// Format a memory address as a memory reference, e.g. `0x7f3a00001c40`.
+///
+/// Memory references are opaque to clients, but `0x`-prefixed hex addresses are the convention
+/// and what users expect to see in memory and disassembly views.
+pub fn memory_reference_from_addr(addr: u64) -> String {
+    format!("{addr:#x}")
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -63,4 +120,39 @@ mod test {
         };
         assert_eq!(version.to_string(), "1.62.1");
     }
+
+    #[test]
+    fn test_version_is_compatible_with() {
+        let version = get_spec_version();
+        assert!(version.is_compatible_with("1.62.x"));
+        assert!(version.is_compatible_with("1.62.3"));
+        assert!(version.is_compatible_with("1.58.0"));
+        assert!(version.is_compatible_with(&version.to_string()));
+        assert!(!version.is_compatible_with("1.65.0"));
+        assert!(!version.is_compatible_with("2.0"));
+        assert!(!version.is_compatible_with("latest"));
+        assert!(!version.is_compatible_with("1"));
+    }
+
+    #[test]
+    fn test_format_type() {
+        assert_eq!(
+            format_type("std::collections", "HashMap", &["String", "i64"]),
+            "std::collections::HashMap<String, i64>"
+        );
+        let inner = format_type("", "Option", &["u8"]);
+        assert_eq!(format_type("", "Vec", &[&inner]), "Vec<Option<u8>>");
+        assert_eq!(format_type("game", "Player", &[]), "game::Player");
+        assert!(type_name_of::<Vec<i32>>().ends_with("Vec<i32>"));
+    }
+
+    #[test]
+    fn test_memory_reference_from_addr() {
+        assert_eq!(
+            memory_reference_from_addr(0x7fff_5fbf_f8a0),
+            "0x7fff5fbff8a0"
+        );
+        assert_eq!(memory_reference_from_addr(u64::MAX), "0xffffffffffffffff");
+        assert_eq!(memory_reference_from_addr(0), "0x0");
+    }
 }